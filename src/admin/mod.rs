@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use tokio::sync::oneshot;
+
+use crate::{common::{from_hex, math::Vector3}, auth};
+
+mod server;
+
+pub use server::{bind, serve};
+
+///
+/// Something an operator asks of the running server.
+/// 
+#[derive(Debug)]
+pub enum Command {
+    List,
+    Kick { id: [u8; 16] },
+    Ban { id: [u8; 16], until: Option<i64>, reason: String },
+    Broadcast { message: String },
+    Teleport { id: [u8; 16], to: Vector3 },
+    Stats,
+}
+
+///
+/// A command on its way into the game loop, with where its answer goes.
+/// 
+pub struct Request {
+    pub command: Command,
+    pub reply: oneshot::Sender<String>,
+}
+
+///
+/// Longest message a broadcast may carry, in bytes, so the notice
+/// with its two bytes of header still fits in a frame.
+/// 
+const MAX_MESSAGE_SIZE: usize = u16::MAX as usize - 2;
+
+pub const HELP: &str = "\
+list                              connections and their positions
+kick <id>                         drop a connection
+ban <id> <seconds|-> <reason>     ban an account for a while or for good
+broadcast <message>               send a message to everyone
+teleport <id> <x> <y> <z>         move a player at once
+stats                             scheduled jobs
+help                              this";
+
+///
+/// Parse a line like `kick 0123...`, where ids are 32 hex digits.
+/// 
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (name, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+
+        let rest = rest.trim();
+
+        let mut words = rest.split_whitespace();
+
+        match name {
+            "list" => Ok(Command::List),
+            "stats" => Ok(Command::Stats),
+            "kick" => Ok(Command::Kick { id: parse_id(words.next())? }),
+            "ban" => {
+                let id = parse_id(words.next())?;
+
+                let until = match words.next() {
+                    Some("-") => None,
+                    Some(seconds) => match seconds.parse::<i64>().ok()
+                        .filter(|seconds| *seconds > 0)
+                        .and_then(|seconds| seconds.checked_mul(1000))
+                        .and_then(|millis| auth::now_millis().checked_add(millis)) {
+                        Some(until) => Some(until),
+                        None => return Err(format!("invalid duration, {seconds:?}")),
+                    },
+                    None => return Err(String::from("missing duration")),
+                };
+
+                let reason = words.collect::<Vec<_>>().join(" ");
+
+                if reason.is_empty() {
+                    return Err(String::from("missing reason"));
+                }
+
+                Ok(Command::Ban { id, until, reason })
+            },
+            "broadcast" if rest.is_empty() => Err(String::from("missing message")),
+            "broadcast" if rest.len() > MAX_MESSAGE_SIZE => Err(format!("message is longer than {MAX_MESSAGE_SIZE} bytes, {}", rest.len())),
+            "broadcast" => Ok(Command::Broadcast { message: rest.to_owned() }),
+            "teleport" => {
+                let id = parse_id(words.next())?;
+
+                let mut coordinate = || match words.next().map(str::parse::<i32>) {
+                    Some(Ok(value)) => Ok(value),
+                    _ => Err(String::from("expected x y z")),
+                };
+
+                let to = Vector3::new(coordinate()?, coordinate()?, coordinate()?);
+
+                Ok(Command::Teleport { id, to })
+            },
+            _ => Err(format!("unknown command, {name:?}")),
+        }
+    }
+}
+
+fn parse_id(word: Option<&str>) -> Result<[u8; 16], String> {
+    let word = word.unwrap_or_default();
+
+    match from_hex(word).and_then(|id| id.try_into().ok()) {
+        Some(id) => Ok(id),
+        None => Err(format!("invalid id, expected 32 hex digits, {word:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a";
+
+    fn error(line: &str) -> String {
+        match line.parse::<Command>() {
+            Ok(command) => panic!("parsed, {command:?}"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn ban_durations_must_be_in_range() {
+        assert!(matches!(format!("ban {ID} 60 spam").parse(), Ok(Command::Ban { until: Some(_), .. })));
+
+        assert!(matches!(format!("ban {ID} - spam").parse(), Ok(Command::Ban { until: None, .. })));
+
+        assert_eq!(error(&format!("ban {ID} 0 spam")), "invalid duration, \"0\"");
+
+        assert_eq!(error(&format!("ban {ID} 9223372036854775807 spam")), "invalid duration, \"9223372036854775807\"");
+
+        assert_eq!(error(&format!("ban {ID} 9223372036854775 spam")), "invalid duration, \"9223372036854775\"");
+    }
+
+    #[test]
+    fn broadcasts_must_fit_in_a_frame() {
+        let longest = "a".repeat(MAX_MESSAGE_SIZE);
+
+        assert!(matches!(format!("broadcast {longest}").parse(), Ok(Command::Broadcast { message }) if message == longest));
+
+        assert_eq!(error(&format!("broadcast {longest}a")), format!("message is longer than {MAX_MESSAGE_SIZE} bytes, {}", MAX_MESSAGE_SIZE + 1));
+
+        assert_eq!(error("broadcast  "), "missing message");
+    }
+}
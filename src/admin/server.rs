@@ -0,0 +1,119 @@
+use std::{fs, io, os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt}, path::Path};
+
+use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::{UnixListener, UnixStream}, sync::{mpsc, oneshot}};
+
+use super::{Command, Request, HELP};
+
+///
+/// Open the admin socket at `path`, in place of one left behind.
+/// 
+/// The socket is only open to its owner, which is all the
+/// authentication it has. It is bound in a directory only the owner
+/// may enter and moved into place once its mode is set, so nobody can
+/// connect while the umask decides who may. Anything at `path` that
+/// is not a socket is left alone and refused.
+/// 
+pub fn bind(path: impl AsRef<Path>) -> io::Result<UnixListener> {
+    let path = path.as_ref();
+
+    let (parent, name) = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => (parent, name),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid admin socket path, {path:?}"))),
+    };
+
+    fs::create_dir_all(parent)?;
+
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{path:?} exists and is not a socket"))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+        Err(e) => return Err(e),
+    }
+
+    let staging = parent.join(format!(".{}.{}", name.to_string_lossy(), std::process::id()));
+
+    // Left behind by a crash, as pids repeat in containers.
+    let _ = fs::remove_dir_all(&staging);
+
+    fs::DirBuilder::new().mode(0o700).create(&staging)?;
+
+    let result = bind_private(&staging.join(name), path);
+
+    let _ = fs::remove_dir_all(&staging);
+
+    result
+}
+
+///
+/// Bind at `staging`, closed to others, and move it to `path`.
+/// 
+fn bind_private(staging: &Path, path: &Path) -> io::Result<UnixListener> {
+    let listener = UnixListener::bind(staging)?;
+
+    fs::set_permissions(staging, fs::Permissions::from_mode(0o600))?;
+
+    fs::rename(staging, path)?;
+
+    Ok(listener)
+}
+
+///
+/// Take commands from every admin, one per line, and pass them
+/// into the game loop through `requests`.
+/// 
+/// Answers end with an empty line.
+/// 
+pub async fn serve(listener: UnixListener, requests: mpsc::Sender<Request>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to accept an admin");
+
+                continue;
+            },
+        };
+
+        let requests = requests.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = session(stream, requests).await {
+                tracing::debug!(error = %e, "admin session ended");
+            }
+        });
+    }
+}
+
+async fn session(stream: UnixStream, requests: mpsc::Sender<Request>) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let answer = match line.trim() {
+            "help" => String::from(HELP),
+            line => match line.parse::<Command>() {
+                Ok(command) => {
+                    tracing::info!(command = line, "admin command");
+
+                    let (reply, answer) = oneshot::channel();
+
+                    if requests.send(Request { command, reply }).await.is_err() {
+                        return Ok(());
+                    }
+
+                    answer.await.unwrap_or_else(|_| String::from("error: the server is shutting down"))
+                },
+                Err(e) => format!("error: {e}"),
+            },
+        };
+
+        writer.write_all(format!("{}\n\n", answer.trim_end()).as_bytes()).await?;
+    }
+
+    Ok(())
+}
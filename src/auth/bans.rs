@@ -0,0 +1,153 @@
+use std::{collections::{HashMap, hash_map::DefaultHasher}, error::Error, fs, hash::{Hash, Hasher}, io::{self, Write}, path::{Path, PathBuf}};
+
+use crate::common::{Bytes, from_hex};
+
+///
+/// Why and until when an account is kept out.
+///
+/// `until` is in milliseconds since the unix epoch, and `None` bans for good.
+///
+#[derive(Debug, Clone)]
+pub struct Ban {
+    pub until: Option<i64>,
+    pub reason: String,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: i64) -> bool {
+        match self.until {
+            Some(until) => now < until,
+            None => true,
+        }
+    }
+}
+
+///
+/// Banned accounts, kept in a file that is read again whenever it changes.
+///
+/// Every line is `<hex id> <until> <reason>`, where `until` is in seconds
+/// since the unix epoch or `-` for a permanent ban, and lines starting
+/// with `//` are comments. A later line of the same id wins, and
+/// a line that cannot be read is skipped with a warning.
+///
+/// Changes are told by the contents rather than the modification time,
+/// which is too coarse to tell apart two edits within a second.
+///
+pub struct BanList {
+    path: PathBuf,
+    digest: Option<u64>,
+    bans: HashMap<[u8; 16], Ban>,
+}
+
+impl BanList {
+    ///
+    /// Read the list, which is empty when the file does not exist yet.
+    ///
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut list = BanList { path, digest: None, bans: HashMap::new() };
+
+        list.reload()?;
+
+        Ok(list)
+    }
+
+    ///
+    /// Read the file again if it changed since the last read, and tell if it did.
+    ///
+    /// On failure the bans read before are kept.
+    ///
+    pub fn reload(&mut self) -> io::Result<bool> {
+        let source = match fs::read_to_string(&self.path) {
+            Ok(source) => Some(source),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        let digest = source.as_deref().map(digest);
+
+        if digest == self.digest {
+            return Ok(false);
+        }
+
+        let mut bans = HashMap::new();
+
+        for (number, line) in source.as_deref().unwrap_or_default().lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with("//") {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some((id, ban)) => { bans.insert(id, ban); },
+                None => tracing::warn!(path = ?self.path, line = number + 1, "skipping malformed ban, {line:?}"),
+            }
+        }
+
+        self.digest = digest;
+
+        self.bans = bans;
+
+        Ok(true)
+    }
+
+    ///
+    /// The ban keeping `id` out at `now`, if any.
+    ///
+    pub fn find(&self, id: &[u8; 16], now: i64) -> Option<&Ban> {
+        self.bans.get(id).filter(|ban| ban.is_active(now))
+    }
+
+    ///
+    /// Ban an account, and write it down so it outlives the server.
+    ///
+    pub fn add(&mut self, id: [u8; 16], ban: Ban) -> io::Result<()> {
+        self.reload()?;
+
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+
+        file.write_all(format_line(&id, &ban).as_bytes())?;
+
+        self.digest = Some(digest(&fs::read_to_string(&self.path)?));
+
+        self.bans.insert(id, ban);
+
+        Ok(())
+    }
+}
+
+fn format_line(id: &[u8; 16], ban: &Ban) -> String {
+    let until = match ban.until {
+        Some(until) => (until / 1000).to_string(),
+        None => String::from("-"),
+    };
+
+    format!("{} {until} {}\n", id.to_hex(), ban.reason.replace('\n', " "))
+}
+
+fn parse_line(line: &str) -> Option<([u8; 16], Ban)> {
+    let mut words = line.split_whitespace();
+
+    let id = from_hex(words.next()?)?.try_into().ok()?;
+
+    let until = match words.next()? {
+        "-" => None,
+        seconds => Some(seconds.parse::<i64>().ok()?.checked_mul(1000)?),
+    };
+
+    let reason = words.collect::<Vec<_>>().join(" ");
+
+    Some((id, Ban { until, reason }))
+}
+
+fn digest(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    source.hash(&mut hasher);
+
+    hasher.finish()
+}
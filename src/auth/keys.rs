@@ -0,0 +1,110 @@
+use std::error::Error;
+
+///
+/// A secret tokens are signed with.
+/// 
+/// `retire_at` is when it stops being accepted, in milliseconds since the unix epoch.
+/// 
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub id: String,
+    pub secret: String,
+    pub retire_at: Option<i64>,
+}
+
+///
+/// Keys accepted for verifying tokens, with the one new tokens are signed with.
+/// 
+#[derive(Debug, Clone)]
+pub struct KeySet {
+    keys: Vec<Key>,
+    active: usize,
+}
+
+impl KeySet {
+    pub fn new(keys: Vec<Key>, active: &str) -> Result<Self, Box<dyn Error>> {
+        let active = match keys.iter().position(|key| key.id == active) {
+            Some(active) => active,
+            None => return Err(format!("unknown active key, {active:?}").into()),
+        };
+
+        if keys[active].retire_at.is_some() {
+            return Err(format!("active key {:?} has a retirement date", keys[active].id).into());
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            if key.id.is_empty() || key.id.contains('.') {
+                return Err(format!("invalid key id, {:?}", key.id).into());
+            }
+
+            if keys[..i].iter().any(|other| other.id == key.id) {
+                return Err(format!("duplicate key id, {:?}", key.id).into());
+            }
+        }
+
+        Ok(KeySet { keys, active })
+    }
+
+    ///
+    /// A set of one key, for a server that never rotated its secret.
+    /// 
+    pub fn single(secret: String) -> Self {
+        KeySet { keys: vec![Key { id: String::from("default"), secret, retire_at: None }], active: 0 }
+    }
+
+    ///
+    /// Parse keys written as `id:retire_at:secret` separated by commas,
+    /// where `retire_at` is in seconds since the unix epoch and may be left empty.
+    /// 
+    pub fn parse(source: &str, active: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let mut keys = vec![];
+
+        for entry in source.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let mut parts = entry.splitn(3, ':');
+
+            let (id, retire_at, secret) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(id), Some(retire_at), Some(secret)) => (id, retire_at, secret),
+                _ => return Err(format!("invalid key, expected id:retire_at:secret, {:?}", entry.split(':').next().unwrap_or_default()).into()),
+            };
+
+            let retire_at = match retire_at {
+                "" => None,
+                seconds => match seconds.parse::<i64>()?.checked_mul(1000) {
+                    Some(millis) => Some(millis),
+                    None => return Err(format!("retire_at of key {id:?} is out of range, {seconds}").into()),
+                },
+            };
+
+            keys.push(Key { id: id.to_owned(), secret: secret.to_owned(), retire_at });
+        }
+
+        if keys.is_empty() {
+            return Err("no keys".into());
+        }
+
+        let active = match active {
+            Some(active) => active.to_owned(),
+            None => keys[0].id.clone(),
+        };
+
+        KeySet::new(keys, &active)
+    }
+
+    pub fn active(&self) -> &Key {
+        &self.keys[self.active]
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Key> {
+        self.keys.iter().find(|key| key.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Key> {
+        self.keys.iter()
+    }
+}
+
+impl Key {
+    pub fn is_retired(&self, now: i64) -> bool {
+        matches!(self.retire_at, Some(retire_at) if retire_at <= now)
+    }
+}
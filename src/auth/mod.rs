@@ -1,51 +1,341 @@
-use std::error::Error;
+use std::{fmt, time::{Duration, SystemTime}};
 
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
+use crate::common::Bytes;
+
+mod keys;
+mod roles;
+mod bans;
+
+pub use keys::{Key, KeySet};
+pub use bans::{Ban, BanList};
+pub use roles::Roles;
+
 type HS256 = Hmac<Sha256>;
 
+///
+/// Length of a signature in base64 without padding.
+/// 
+const SIGNATURE_LENGTH: usize = 43;
+
+///
+/// Version of the payload new tokens are issued with.
+/// 
+const VERSION: u8 = 1;
+
+///
+/// Identity of a player, issued by the login service.
+/// 
+/// `timestamp` is when it was issued, in milliseconds since the unix epoch.
+/// 
 #[derive(Debug)]
 pub struct Token {
     pub id: [u8; 16],
     pub timestamp: i64,
+    pub roles: Roles,
 }
 
-pub fn verify(input: &str, secret: &str) -> Result<Token, Box<dyn Error>> {
-    if input.len() < 32 {
-        return Err("token too short".into());
+#[derive(Debug)]
+pub enum Error {
+    Malformed(String),
+    UnknownKey(String),
+    InvalidKey { id: Option<String>, reason: String },
+    RetiredKey(String),
+    InvalidSignature,
+    Expired { age: Duration },
+    IssuedInFuture { ahead: Duration },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Malformed(reason) => write!(f, "malformed token, {reason}"),
+            Error::UnknownKey(id) => write!(f, "token signed with unknown key, {id:?}"),
+            Error::InvalidKey { id: Some(id), reason } => write!(f, "invalid secret of key {id:?}, {reason}"),
+            Error::InvalidKey { id: None, reason } => write!(f, "invalid secret, {reason}"),
+            Error::RetiredKey(id) => write!(f, "token signed with retired key, {id:?}"),
+            Error::InvalidSignature => write!(f, "invalid token signature"),
+            Error::Expired { age } => write!(f, "token expired, issued {}ms ago", age.as_millis()),
+            Error::IssuedInFuture { ahead } => write!(f, "token issued {}ms in the future", ahead.as_millis()),
+        }
     }
+}
+
+impl std::error::Error for Error {}
 
-    let mut payload: Vec<u8> = vec![];
+///
+/// Check the signature of a token, and that it was issued
+/// no longer than `max_age` ago nor later than `skew` from now.
+/// 
+/// A token is `<key id>.<payload><signature>` in base64, and without
+/// the key id every key that is not retired yet is tried.
+/// 
+pub fn verify(input: &str, keys: &KeySet, max_age: Duration, skew: Duration) -> Result<Token, Error> {
+    let now = now_millis();
+
+    let (key_id, input) = match input.split_once('.') {
+        Some((key_id, input)) => (Some(key_id), input),
+        None => (None, input),
+    };
+
+    if input.len() <= SIGNATURE_LENGTH || !input.is_char_boundary(input.len() - SIGNATURE_LENGTH) {
+        return Err(Error::Malformed(String::from("too short")));
+    }
+
+    let (encoded, signature) = input.split_at(input.len() - SIGNATURE_LENGTH);
+
+    let payload = match base64::decode_config(encoded, base64::URL_SAFE) {
+        Ok(payload) => payload,
+        Err(e) => return Err(Error::Malformed(e.to_string())),
+    };
+
+    let signature = match base64::decode_config(signature, base64::URL_SAFE_NO_PAD) {
+        Ok(signature) => signature,
+        Err(e) => return Err(Error::Malformed(format!("signature, {e}"))),
+    };
+
+    let candidates = match key_id {
+        Some(key_id) => match keys.get(key_id) {
+            Some(key) if key.is_retired(now) => return Err(Error::RetiredKey(key.id.clone())),
+            Some(key) => vec![key],
+            None => return Err(Error::UnknownKey(key_id.to_owned())),
+        },
+        None => keys.iter().filter(|key| !key.is_retired(now)).collect(),
+    };
+
+    let mut is_signed = false;
+
+    for key in candidates {
+        let mut mac = new_mac(Some(&key.id), &key.secret)?;
+
+        mac.update(&payload);
+
+        // Compared in constant time, so a forger learns nothing from how long it took.
+        if mac.verify_slice(&signature).is_ok() {
+            is_signed = true;
+
+            break;
+        }
+    }
+
+    if !is_signed {
+        return Err(Error::InvalidSignature);
+    }
 
-    base64::decode_config_buf(&input[..32], base64::URL_SAFE, &mut payload)?;
+    let token = decode(&payload)?;
 
-    let mut mac = HS256::new_from_slice(secret.as_bytes())?;
+    check_age(token.timestamp, now, max_age, skew)?;
+
+    Ok(token)
+}
+
+///
+/// Make a token for `id` issued at `timestamp`, the inverse of `verify`.
+/// 
+/// It carries no key id, so it is accepted as long as any key with
+/// the same secret is not retired.
+/// 
+pub fn issue(id: [u8; 16], timestamp: i64, roles: Roles, secret: &str) -> Result<String, Error> {
+    seal(id, timestamp, roles, new_mac(None, secret)?)
+}
+
+///
+/// Make a token signed with `key`, prefixed with its id.
+/// 
+pub fn issue_with_key(id: [u8; 16], timestamp: i64, roles: Roles, key: &Key) -> Result<String, Error> {
+    Ok(format!("{}.{}", key.id, seal(id, timestamp, roles, new_mac(Some(&key.id), &key.secret)?)?))
+}
+
+fn seal(id: [u8; 16], timestamp: i64, roles: Roles, mut mac: HS256) -> Result<String, Error> {
+    let payload = [&[VERSION] as &[u8], &id, &timestamp.to_le_bytes(), &roles.bits().to_le_bytes()].concat();
 
     mac.update(&payload);
 
-    let signature = mac.finalize().into_bytes();
+    let signature = base64::encode_config(mac.finalize().into_bytes(), base64::URL_SAFE_NO_PAD);
+
+    Ok(base64::encode_config(payload, base64::URL_SAFE) + &signature)
+}
+
+///
+/// Read a payload by its layout.
+/// 
+/// The first tokens were a bare `<id><timestamp>` of 24 bytes, which
+/// are taken as holding no roles. Every later layout starts with
+/// its version, `1` being `<version><id><timestamp><roles>`.
+/// 
+fn decode(payload: &[u8]) -> Result<Token, Error> {
+    let body = match payload.len() {
+        24 => payload,
+        0 => return Err(Error::Malformed(String::from("empty payload"))),
+        _ if payload[0] == 1 && payload.len() == 29 => &payload[1..],
+        _ if payload[0] == 1 => return Err(Error::Malformed(format!("payload of version 1 has {} bytes", payload.len()))),
+        _ => return Err(Error::Malformed(format!("unknown payload version, {}", payload[0]))),
+    };
+
+    let id = body[..16].clone_into_array();
+
+    let timestamp = i64::from_le_bytes(body[16..24].clone_into_array());
 
-    let expect = &input[32..];
+    let roles = match body.get(24..28) {
+        Some(bits) => Roles::from_bits(u32::from_le_bytes(bits.clone_into_array())),
+        None => Roles::NONE,
+    };
 
-    let actual = base64::encode_config(signature, base64::URL_SAFE_NO_PAD);
+    Ok(Token { id, timestamp, roles })
+}
+
+///
+/// A MAC keyed with `secret`, where `id` is only used to tell
+/// which key was at fault.
+/// 
+fn new_mac(id: Option<&str>, secret: &str) -> Result<HS256, Error> {
+    match HS256::new_from_slice(secret.as_bytes()) {
+        Ok(mac) => Ok(mac),
+        Err(e) => Err(Error::InvalidKey { id: id.map(str::to_owned), reason: e.to_string() }),
+    }
+}
+
+fn check_age(timestamp: i64, now: i64, max_age: Duration, skew: Duration) -> Result<(), Error> {
+    let age = now.saturating_sub(timestamp);
+
+    if age < 0 {
+        let ahead = Duration::from_millis(age.unsigned_abs());
 
-    if expect != actual {
-        return Err(format!("invalid token, \nexpect: {expect:?}\nactual: {actual:?}", ).into());
+        if ahead > skew {
+            return Err(Error::IssuedInFuture { ahead });
+        }
+
+        return Ok(());
     }
 
-    let mut id = [0 as u8; 16];
+    let age = Duration::from_millis(age as u64);
+
+    if age > max_age {
+        return Err(Error::Expired { age });
+    }
 
-    id.swap_with_slice(&mut payload[..16]);
+    Ok(())
+}
 
-    let mut timestamp = [0 as u8; 8];
+pub fn now_millis() -> i64 {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as i64,
+        Err(_) => 0,
+    }
+}
 
-    timestamp.swap_with_slice(&mut payload[16..24]);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let timestamp = i64::from_le_bytes(timestamp);
+    const DAY: Duration = Duration::from_secs(86_400);
 
-    Ok(Token {
-        id,
-        timestamp
-    })
+    const SKEW: Duration = Duration::from_secs(5);
+
+    const ID: [u8; 16] = [7; 16];
+
+    fn keys() -> KeySet {
+        KeySet::parse("new::newsecret,old:1:oldsecret,spare::sparesecret", Some("new")).unwrap()
+    }
+
+    #[test]
+    fn issued_tokens_verify() {
+        let token = issue_with_key(ID, now_millis(), Roles::MODERATOR, keys().active()).unwrap();
+
+        let token = verify(&token, &keys(), DAY, SKEW).unwrap();
+
+        assert_eq!((token.id, token.roles), (ID, Roles::MODERATOR));
+
+        // Without a key id, every key that is not retired is tried.
+        let token = issue(ID, now_millis(), Roles::NONE, "sparesecret").unwrap();
+
+        assert_eq!(verify(&token, &keys(), DAY, SKEW).unwrap().id, ID);
+    }
+
+    #[test]
+    fn first_tokens_without_version_hold_no_roles() {
+        let payload = [&ID as &[u8], &now_millis().to_le_bytes()].concat();
+
+        let mut mac = new_mac(None, "newsecret").unwrap();
+
+        mac.update(&payload);
+
+        let token = base64::encode_config(&payload, base64::URL_SAFE) + &base64::encode_config(mac.finalize().into_bytes(), base64::URL_SAFE_NO_PAD);
+
+        assert_eq!(verify(&token, &keys(), DAY, SKEW).unwrap().roles, Roles::NONE);
+    }
+
+    #[test]
+    fn forged_tokens_are_refused() {
+        let token = issue(ID, now_millis(), Roles::NONE, "othersecret").unwrap();
+
+        assert!(matches!(verify(&token, &keys(), DAY, SKEW), Err(Error::InvalidSignature)));
+
+        // The payload is changed under the signature of another.
+        let genuine = issue(ID, now_millis(), Roles::NONE, "newsecret").unwrap();
+
+        let elevated = issue(ID, now_millis(), Roles::GAME_MASTER, "othersecret").unwrap();
+
+        let forged = elevated[..elevated.len() - SIGNATURE_LENGTH].to_owned() + &genuine[genuine.len() - SIGNATURE_LENGTH..];
+
+        assert!(matches!(verify(&forged, &keys(), DAY, SKEW), Err(Error::InvalidSignature)));
+
+        assert!(matches!(verify("short", &keys(), DAY, SKEW), Err(Error::Malformed(_))));
+
+        let unencoded = genuine[..genuine.len() - 1].to_owned() + "*";
+
+        assert!(matches!(verify(&unencoded, &keys(), DAY, SKEW), Err(Error::Malformed(_))));
+    }
+
+    #[test]
+    fn key_ids_pick_the_key() {
+        let keys = keys();
+
+        let old = issue_with_key(ID, now_millis(), Roles::NONE, keys.get("old").unwrap()).unwrap();
+
+        assert!(matches!(verify(&old, &keys, DAY, SKEW), Err(Error::RetiredKey(id)) if id == "old"));
+
+        let unknown = format!("gone.{}", issue(ID, now_millis(), Roles::NONE, "newsecret").unwrap());
+
+        assert!(matches!(verify(&unknown, &keys, DAY, SKEW), Err(Error::UnknownKey(id)) if id == "gone"));
+
+        // Signed with a valid key, but named after another.
+        let misnamed = format!("spare.{}", issue(ID, now_millis(), Roles::NONE, "newsecret").unwrap());
+
+        assert!(matches!(verify(&misnamed, &keys, DAY, SKEW), Err(Error::InvalidSignature)));
+    }
+
+    #[test]
+    fn age_is_bounded_both_ways() {
+        let now = now_millis();
+
+        let old = issue(ID, now - 2 * DAY.as_millis() as i64, Roles::NONE, "newsecret").unwrap();
+
+        assert!(matches!(verify(&old, &keys(), DAY, SKEW), Err(Error::Expired { .. })));
+
+        let early = issue(ID, now + 60_000, Roles::NONE, "newsecret").unwrap();
+
+        assert!(matches!(verify(&early, &keys(), DAY, SKEW), Err(Error::IssuedInFuture { .. })));
+
+        let skewed = issue(ID, now + 1_000, Roles::NONE, "newsecret").unwrap();
+
+        assert!(verify(&skewed, &keys(), DAY, SKEW).is_ok());
+    }
+
+    #[test]
+    fn times_out_of_range_are_refused() {
+        let error = KeySet::parse("old:9223372036854775807:oldsecret", None).err().unwrap();
+
+        assert_eq!(error.to_string(), "retire_at of key \"old\" is out of range, 9223372036854775807");
+
+        let data = tempfile::tempdir().unwrap();
+
+        let path = data.path().join("bans");
+
+        std::fs::write(&path, format!("{} 9223372036854775807 forever\n", ID.to_hex())).unwrap();
+
+        assert!(BanList::open(&path).unwrap().find(&ID, now_millis()).is_none());
+    }
 }
@@ -0,0 +1,72 @@
+use std::{error::Error, fmt, ops::BitOr, str::FromStr};
+
+///
+/// What an account may do beyond playing, as a bitfield.
+///
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Roles(u32);
+
+impl Roles {
+    pub const NONE: Roles = Roles(0);
+    pub const MODERATOR: Roles = Roles(0b01);
+    pub const GAME_MASTER: Roles = Roles(0b10);
+
+    const NAMES: [(Roles, &'static str); 2] = [
+        (Roles::MODERATOR, "moderator"),
+        (Roles::GAME_MASTER, "game-master"),
+    ];
+
+    pub fn from_bits(bits: u32) -> Self {
+        Roles(bits)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    ///
+    /// Whether every role of `other` is held.
+    ///
+    pub fn contains(self, other: Roles) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Roles {
+    type Output = Roles;
+
+    fn bitor(self, rhs: Roles) -> Roles {
+        Roles(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for Roles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Roles::NAMES.iter().filter(|(role, _)| self.contains(*role)).map(|(_, name)| *name).collect::<Vec<_>>();
+
+        match names.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", names.join(",")),
+        }
+    }
+}
+
+///
+/// Parse role names separated by commas, or `none`.
+///
+impl FromStr for Roles {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut roles = Roles::NONE;
+
+        for name in s.split(',').map(str::trim).filter(|name| !name.is_empty() && *name != "none") {
+            match Roles::NAMES.iter().find(|(_, known)| *known == name) {
+                Some((role, _)) => roles = roles | *role,
+                None => return Err(format!("unknown role, {name:?}").into()),
+            }
+        }
+
+        Ok(roles)
+    }
+}
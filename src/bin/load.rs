@@ -0,0 +1,266 @@
+use std::{collections::BTreeMap, error::Error, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use mmorpg::{auth::{self, Roles}, client::Client, constants::Constants, net::packet::Outgoing};
+use tokio::time::{self, Instant};
+
+const USAGE: &str = "usage: load [--clients <n>] [--rate <moves per second>] [--duration <seconds>] [--ramp <seconds>] [--address <host:port>]";
+
+///
+/// Settings of a run, from the command line.
+///
+struct Options {
+    clients: u32,
+    rate: f64,
+    duration: Duration,
+    ramp: Duration,
+    address: Option<String>,
+}
+
+///
+/// What a single player went through.
+///
+#[derive(Default)]
+struct Report {
+    connect: Option<Duration>,
+    rtts: Vec<Duration>,
+    moves: Vec<Duration>,
+    unanswered: usize,
+    ended: Option<String>,
+}
+
+///
+/// Simulate players walking around on a local server, and
+/// tell how fast it answered them.
+///
+/// Tokens are signed with the active key of the same settings the server
+/// reads, and every player walks a tile into a random direction at
+/// `--rate`, stopping as soon as the server broadcasts the step.
+///
+/// Usage: `load [--clients <n>] [--rate <moves per second>] [--duration <seconds>] [--ramp <seconds>] [--address <host:port>]`
+///
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let options = parse_options()?;
+
+    let constants = Constants::init()?;
+
+    let address = options.address.clone().unwrap_or_else(|| format!("127.0.0.1:{}", constants.listen_address.port()));
+
+    let period = Duration::from_secs_f64(1.0 / options.rate);
+
+    let start = Instant::now();
+
+    let until = start + options.ramp + options.duration;
+
+    println!("{} clients against {address}, a move every {}ms each, for {}s after {}s of ramp", options.clients, period.as_millis(), options.duration.as_secs(), options.ramp.as_secs());
+
+    let mut bots = Vec::new();
+
+    for index in 0..options.clients {
+        let mut id = *b"load\0\0\0\0\0\0\0\0\0\0\0\0";
+
+        id[12..].copy_from_slice(&index.to_be_bytes());
+
+        let token = auth::issue_with_key(id, auth::now_millis(), Roles::NONE, constants.auth_keys.active())?;
+
+        let delay = options.ramp.mul_f64(f64::from(index) / f64::from(options.clients));
+
+        bots.push(tokio::spawn(bot(index, address.clone(), token, start + delay, period, until, start)));
+    }
+
+    let mut reports = Vec::new();
+
+    for bot in bots {
+        reports.push(bot.await?);
+    }
+
+    print_summary(&reports);
+
+    Ok(())
+}
+
+fn parse_options() -> Result<Options, Box<dyn Error>> {
+    let mut options = Options { clients: 100, rate: 1.0, duration: Duration::from_secs(30), ramp: Duration::from_secs(5), address: None };
+
+    let mut args = std::env::args().skip(1);
+
+    while let Some(flag) = args.next() {
+        let value = match args.next() {
+            Some(value) => value,
+            None => return Err(USAGE.into()),
+        };
+
+        match flag.as_str() {
+            "--clients" => options.clients = value.parse()?,
+            "--rate" => options.rate = value.parse()?,
+            "--duration" => options.duration = Duration::from_secs(value.parse()?),
+            "--ramp" => options.ramp = Duration::from_secs(value.parse()?),
+            "--address" => options.address = Some(value),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    if options.clients == 0 || !options.rate.is_finite() || options.rate <= 0.0 {
+        return Err(format!("clients and rate must be positive\n{USAGE}").into());
+    }
+
+    Ok(options)
+}
+
+async fn bot(index: u32, address: String, token: String, at: Instant, period: Duration, until: Instant, start: Instant) -> Report {
+    let mut report = Report::default();
+
+    time::sleep_until(at).await;
+
+    let connecting = Instant::now();
+
+    let mut client = match Client::connect(&address, &token).await {
+        Ok(client) => client,
+        Err(e) => {
+            report.ended = Some(format!("connect failed, {e}"));
+
+            return report;
+        },
+    };
+
+    report.connect = Some(connecting.elapsed());
+
+    let mut rng = Rng::new(index);
+
+    if let Err(ended) = play(&mut client, &mut rng, &mut report, period, until, start).await {
+        report.ended = Some(ended);
+
+        return report;
+    }
+
+    let _ = client.close().await;
+
+    report
+}
+
+///
+/// Walk and ping until `until`, or until the server hangs up,
+/// which is returned as why.
+///
+async fn play(client: &mut Client, rng: &mut Rng, report: &mut Report, period: Duration, until: Instant, start: Instant) -> Result<(), String> {
+    let id = client.id();
+
+    // Players start out of step, so the server sees an even stream of moves.
+    let mut ticker = time::interval_at(Instant::now() + period.mul_f64(rng.fraction()), period);
+
+    let mut pending: Option<Instant> = None;
+
+    loop {
+        tokio::select! {
+            _ = time::sleep_until(until) => return Ok(()),
+            _ = ticker.tick() => {
+                // The step before got no answer, it may have been too soon after the last one.
+                if pending.is_some() {
+                    report.unanswered += 1;
+                }
+
+                client.walk(rng.direction()).await.map_err(|e| e.to_string())?;
+
+                pending = Some(Instant::now());
+
+                let timestamp = i64::try_from(start.elapsed().as_micros()).unwrap_or(i64::MAX);
+
+                client.ping(timestamp).await.map_err(|e| e.to_string())?;
+            },
+            packet = client.next() => match packet {
+                Ok(Some(Outgoing::Pong { timestamp })) => {
+                    let sent = Duration::from_micros(u64::try_from(timestamp).unwrap_or_default());
+
+                    report.rtts.push(start.elapsed().saturating_sub(sent));
+                },
+                Ok(Some(Outgoing::Move { id: mover, .. } | Outgoing::Arrive { id: mover, .. })) if mover == id => {
+                    if let Some(sent) = pending.take() {
+                        report.moves.push(sent.elapsed());
+
+                        client.walk(0).await.map_err(|e| e.to_string())?;
+                    }
+                },
+                Ok(Some(Outgoing::Kick { reason })) => return Err(format!("kicked, {reason:?}")),
+                Ok(Some(Outgoing::Refuse { reason })) => return Err(format!("refused, {reason:?}")),
+                Ok(Some(Outgoing::Shutdown { .. })) => return Err(String::from("server shutting down")),
+                Ok(Some(_)) => {},
+                Ok(None) => return Err(String::from("closed by the server")),
+                Err(e) => return Err(e.to_string()),
+            },
+        }
+    }
+}
+
+fn print_summary(reports: &[Report]) {
+    let connected = reports.iter().filter(|report| report.connect.is_some()).count();
+
+    println!("connected {connected} of {}", reports.len());
+
+    let connects = reports.iter().filter_map(|report| report.connect).collect::<Vec<_>>();
+
+    print_latencies("connect", connects);
+
+    print_latencies("pong rtt", reports.iter().flat_map(|report| report.rtts.iter().copied()).collect());
+
+    print_latencies("move", reports.iter().flat_map(|report| report.moves.iter().copied()).collect());
+
+    println!("moves without broadcast {}", reports.iter().map(|report| report.unanswered).sum::<usize>());
+
+    let mut endings: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for ended in reports.iter().filter_map(|report| report.ended.as_deref()) {
+        *endings.entry(ended).or_default() += 1;
+    }
+
+    println!("disconnects {}", endings.values().sum::<usize>());
+
+    for (ended, count) in endings {
+        println!("  {count} {ended}");
+    }
+}
+
+///
+/// Print how many samples there are and how they spread, in milliseconds.
+///
+fn print_latencies(name: &str, mut samples: Vec<Duration>) {
+    if samples.is_empty() {
+        println!("{name:<8} none");
+
+        return;
+    }
+
+    samples.sort();
+
+    let at = |percent: usize| samples[(samples.len() - 1) * percent / 100].as_secs_f64() * 1000.0;
+
+    println!("{name:<8} n={} min={:.2} p50={:.2} p90={:.2} p99={:.2} max={:.2}", samples.len(), at(0), at(50), at(90), at(99), at(100));
+}
+
+///
+/// Xorshift, enough to spread players over directions without a dependency.
+///
+struct Rng(u64);
+
+impl Rng {
+    fn new(index: u32) -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.subsec_nanos()).unwrap_or_default();
+
+        Rng((u64::from(index) << 32 | u64::from(nanos)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        self.0
+    }
+
+    fn direction(&mut self) -> u8 {
+        1 + (self.next() % 4) as u8
+    }
+
+    fn fraction(&mut self) -> f64 {
+        (self.next() % 1000) as f64 / 1000.0
+    }
+}
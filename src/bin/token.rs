@@ -0,0 +1,48 @@
+use std::error::Error;
+
+use mmorpg::{auth::{self, Roles}, common::from_hex, constants::Constants};
+
+const USAGE: &str = "usage: token <hex account id> [--roles <role,...>] [--at <milliseconds>]";
+
+///
+/// Print a token for an account, signed with the active key
+/// of the same settings the server reads.
+///
+/// Usage: `token <hex account id> [--roles <role,...>] [--at <milliseconds>]`
+///
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+
+    let hex = match args.next() {
+        Some(hex) => hex,
+        None => return Err(USAGE.into()),
+    };
+
+    let id = match from_hex(&hex).and_then(|id| id.try_into().ok()) {
+        Some(id) => id,
+        None => return Err(format!("invalid account id, expected 32 hex digits, {hex:?}").into()),
+    };
+
+    let mut roles = Roles::NONE;
+
+    let mut timestamp = auth::now_millis();
+
+    while let Some(flag) = args.next() {
+        let value = match args.next() {
+            Some(value) => value,
+            None => return Err(USAGE.into()),
+        };
+
+        match flag.as_str() {
+            "--roles" => roles = value.parse()?,
+            "--at" => timestamp = value.parse()?,
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let constants = Constants::init()?;
+
+    println!("{}", auth::issue_with_key(id, timestamp, roles, constants.auth_keys.active())?);
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use std::fmt;
+
+use crate::net::{self, packet::{Outgoing, Reason}};
+
+///
+/// Failure of a client.
+/// 
+#[derive(Debug)]
+pub enum Error {
+    Net(net::Error),
+    Refused(Reason),
+    Closed,
+    Unexpected(Outgoing),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Net(e) => write!(f, "{e}"),
+            Error::Refused(reason) => write!(f, "refused by the server, {reason:?}"),
+            Error::Closed => write!(f, "connection closed by the server"),
+            Error::Unexpected(packet) => write!(f, "unexpected packet arrived, {packet:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<net::Error> for Error {
+    fn from(e: net::Error) -> Self {
+        Error::Net(e)
+    }
+}
+
+impl From<net::DecodeError> for Error {
+    fn from(e: net::DecodeError) -> Self {
+        Error::Net(net::Error::Decode(e))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Net(net::Error::Io(e))
+    }
+}
@@ -0,0 +1,111 @@
+mod error;
+
+pub use error::Error;
+
+use tokio::{io::AsyncWriteExt, net::{TcpStream, ToSocketAddrs}};
+
+use crate::net::{io::{self as frame, Decoder}, packet::{Incoming, Outgoing}};
+
+///
+/// Largest frame the server may send, as lengths are 16 bits.
+/// 
+const MAX_FRAME_SIZE: usize = u16::MAX as usize;
+
+///
+/// A player without a screen, speaking the protocol over a socket.
+/// 
+/// Everything the server sends comes out of `next` as the same
+/// `Outgoing` packets the server builds, already decoded.
+/// 
+pub struct Client {
+    id: [u8; 16],
+    stream: TcpStream,
+    decoder: Decoder,
+}
+
+impl Client {
+    ///
+    /// Connect and say hello with `token`, returning once the server
+    /// accepted it.
+    /// 
+    /// The server may still refuse the session right after,
+    /// which arrives as an `Outgoing::Refuse` from `next`.
+    /// 
+    pub async fn connect(address: impl ToSocketAddrs, token: &str) -> Result<Self, Error> {
+        let stream = TcpStream::connect(address).await?;
+
+        stream.set_nodelay(true)?;
+
+        let mut client = Client { id: [0; 16], stream, decoder: Decoder::new(MAX_FRAME_SIZE) };
+
+        client.send(Incoming::Hello { token: token.to_owned() }).await?;
+
+        match client.next().await? {
+            Some(Outgoing::Hello { id }) => client.id = id,
+            Some(Outgoing::Refuse { reason }) => return Err(Error::Refused(reason)),
+            Some(packet) => return Err(Error::Unexpected(packet)),
+            None => return Err(Error::Closed),
+        }
+
+        Ok(client)
+    }
+
+    ///
+    /// The account the server knows this client as.
+    /// 
+    pub fn id(&self) -> [u8; 16] {
+        self.id
+    }
+
+    ///
+    /// Ask for a pong carrying `timestamp` back.
+    /// 
+    pub async fn ping(&mut self, timestamp: i64) -> Result<(), Error> {
+        self.send(Incoming::Ping { timestamp }).await
+    }
+
+    ///
+    /// Start walking, or stop with `0`.
+    /// 
+    /// `1` and `2` walk towards greater and smaller z, `3` and `4` towards smaller and greater x.
+    /// 
+    pub async fn walk(&mut self, direction: u8) -> Result<(), Error> {
+        self.send(Incoming::Move { direction }).await
+    }
+
+    pub async fn send(&mut self, packet: Incoming) -> Result<(), Error> {
+        let mut buf = Vec::new();
+
+        frame::encode_into(&mut buf, &packet.serialize())?;
+
+        self.stream.write_all(&buf).await?;
+
+        Ok(())
+    }
+
+    ///
+    /// Wait for the next packet, `None` once the server closed the connection.
+    /// 
+    /// Cancelling it loses nothing, so it can be raced against other futures.
+    /// 
+    pub async fn next(&mut self) -> Result<Option<Outgoing>, Error> {
+        loop {
+            if let Some(buf) = self.decoder.next_frame()? {
+                return Ok(Some(Outgoing::deserialize(&buf)?));
+            }
+
+            if self.decoder.read_from(&mut self.stream).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    ///
+    /// Close the connection, letting the server see it end.
+    /// 
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.stream.shutdown().await?;
+
+        Ok(())
+    }
+}
@@ -4,6 +4,8 @@ pub trait Bytes {
     fn to_sized(&self, size: usize) -> Vec<u8>;
 
     fn clone_into_array<T>(&self) -> T where T: Sized + Default + AsMut<[u8]>;
+
+    fn to_hex(&self) -> String;
 }
 
 impl Bytes for [u8] {
@@ -16,7 +18,7 @@ impl Bytes for [u8] {
             }
         }
 
-        &self
+        self
     }
 
     fn to_sized(&self, size: usize) -> Vec<u8> {
@@ -40,4 +42,15 @@ impl Bytes for [u8] {
         
         result
     }
+
+    fn to_hex(&self) -> String {
+        self.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+///
+/// Parse bytes written as two hex digits each.
+///
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
 }
@@ -1,5 +1,5 @@
 mod bytes;
 
-pub use bytes::Bytes;
+pub use bytes::{Bytes, from_hex};
 
 pub mod math;
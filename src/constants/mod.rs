@@ -1,13 +1,332 @@
-use std::error::Error;
+use std::{error::Error, fs, io, net::SocketAddr, str::FromStr, time::Duration};
 
+use crate::{auth::KeySet, logging, map::spawn::Policy};
+
+///
+/// What to do when an account logs in while its session is still alive.
+/// 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DuplicateSession {
+    Replace,
+    Refuse,
+}
+
+impl FromStr for DuplicateSession {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replace" => Ok(DuplicateSession::Replace),
+            "refuse" => Ok(DuplicateSession::Refuse),
+            _ => Err(format!("unknown duplicate session policy, {s:?}").into()),
+        }
+    }
+}
+
+///
+/// What the environment variables overriding settings start with.
+/// 
+const ENVIRONMENT_PREFIX: &str = "GOGURYEO_";
+
+///
+/// Variables read before the prefix, still taken for their key
+/// when the prefixed one is not set.
+/// 
+const LEGACY_VARIABLES: &[(&str, &str)] = &[("auth_secret", "AUTH_SECRET")];
+
+///
+/// Settings of the server, checked once at startup.
+/// 
+/// They are read from the TOML file at `CONFIG_PATH`, `config.toml`
+/// by default, where every key may be left out for its default.
+/// An environment variable named after a key in upper case behind
+/// `GOGURYEO_`, like `GOGURYEO_VIEW_RADIUS` for `view_radius`, wins
+/// over the file. The prefix keeps unrelated variables such as `USER`
+/// from being taken for settings. `AUTH_SECRET` is still read for
+/// `auth_secret` when `GOGURYEO_AUTH_SECRET` is not set.
+/// 
 pub struct Constants {
-    pub auth_secret: String,
+    pub listen_address: SocketAddr,
+    pub metrics_address: SocketAddr,
+    pub admin_socket: String,
+    pub auth_keys: KeySet,
+    pub outbound_high_water: usize,
+    pub max_packet_size: usize,
+    pub view_radius: i32,
+    pub movement_tick: Duration,
+    pub map_path: String,
+    pub spawn_region: Option<String>,
+    pub spawn_policy: Policy,
+    pub storage_path: String,
+    pub autosave_interval: Duration,
+    pub token_max_age: Duration,
+    pub token_clock_skew: Duration,
+    pub handshake_timeout: Duration,
+    pub max_waitings: usize,
+    pub duplicate_session: DuplicateSession,
+    pub bans_path: String,
+    pub bans_reload_interval: Duration,
+    pub shutdown_grace_period: Duration,
+    pub reconnect_hint: Duration,
+    pub log_level: String,
+    pub log_format: logging::Format,
 }
 
 impl Constants {
     pub fn init() -> Result<Self, Box<dyn Error>> {
-        let auth_secret = std::env::var("AUTH_SECRET")?;
+        Constants::read(Settings::open()?)
+    }
 
-        Ok(Constants { auth_secret })
+    ///
+    /// Settings given as TOML rather than read from the file,
+    /// with the environment left out so they are the same on every machine.
+    /// 
+    pub fn from_toml(source: &str) -> Result<Self, Box<dyn Error>> {
+        Constants::read(Settings::parse(source, "source", false)?)
     }
-}
\ No newline at end of file
+
+    fn read(mut settings: Settings) -> Result<Self, Box<dyn Error>> {
+        let listen_address = settings.get("listen_address")?.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 3000)));
+
+        let metrics_address = settings.get("metrics_address")?.unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 9100)));
+
+        let admin_socket = settings.get("admin_socket")?.unwrap_or_else(|| String::from("data/admin.sock"));
+
+        let auth_keys = match settings.get::<String>("auth_keys")? {
+            Some(value) => KeySet::parse(&value, settings.get::<String>("auth_active_key")?.as_deref())?,
+            None => match settings.get("auth_secret")? {
+                Some(secret) => KeySet::single(secret),
+                None => return Err("missing auth_secret or auth_keys".into()),
+            },
+        };
+
+        let outbound_high_water = settings.get("outbound_high_water")?.unwrap_or(256 * 1024);
+
+        let max_packet_size = settings.get("max_packet_size")?.unwrap_or(8096);
+
+        let view_radius = settings.get("view_radius")?.unwrap_or(10);
+
+        let movement_tick = Duration::from_millis(settings.get("movement_tick")?.unwrap_or(300));
+
+        let map_path = settings.get("map_path")?.unwrap_or_else(|| String::from("maps/default.map"));
+
+        let spawn_region = settings.get("spawn_region")?;
+
+        let spawn_policy = settings.get("spawn_policy")?.unwrap_or(Policy::LeastCrowded);
+
+        let storage_path = settings.get("storage_path")?.unwrap_or_else(|| String::from("data/characters"));
+
+        let autosave_interval = Duration::from_secs(settings.get("autosave_interval")?.unwrap_or(60));
+
+        let token_max_age = Duration::from_secs(settings.get("token_max_age")?.unwrap_or(24 * 60 * 60));
+
+        let token_clock_skew = Duration::from_secs(settings.get("token_clock_skew")?.unwrap_or(30));
+
+        let handshake_timeout = Duration::from_secs(settings.get("handshake_timeout")?.unwrap_or(10));
+
+        let max_waitings = settings.get("max_waitings")?.unwrap_or(1024);
+
+        let duplicate_session = settings.get("duplicate_session")?.unwrap_or(DuplicateSession::Replace);
+
+        let bans_path = settings.get("bans_path")?.unwrap_or_else(|| String::from("data/bans"));
+
+        let bans_reload_interval = Duration::from_secs(settings.get("bans_reload_interval")?.unwrap_or(5));
+
+        let shutdown_grace_period = Duration::from_secs(settings.get("shutdown_grace_period")?.unwrap_or(10));
+
+        let reconnect_hint = Duration::from_secs(settings.get("reconnect_hint")?.unwrap_or(30));
+
+        let log_level = settings.get("log_level")?.unwrap_or_else(|| String::from("info"));
+
+        let log_format = settings.get("log_format")?.unwrap_or(logging::Format::Human);
+
+        settings.finish()?;
+
+        let constants = Constants {
+            listen_address,
+            metrics_address,
+            admin_socket,
+            auth_keys,
+            outbound_high_water,
+            max_packet_size,
+            view_radius,
+            movement_tick,
+            map_path,
+            spawn_region,
+            spawn_policy,
+            storage_path,
+            autosave_interval,
+            token_max_age,
+            token_clock_skew,
+            handshake_timeout,
+            max_waitings,
+            duplicate_session,
+            bans_path,
+            bans_reload_interval,
+            shutdown_grace_period,
+            reconnect_hint,
+            log_level,
+            log_format,
+        };
+
+        constants.check()?;
+
+        Ok(constants)
+    }
+
+    ///
+    /// Reject values the server cannot run with.
+    /// 
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.max_packet_size == 0 || self.max_packet_size > usize::from(u16::MAX) {
+            return Err(format!("max_packet_size must be between 1 and {}, {}", u16::MAX, self.max_packet_size).into());
+        }
+
+        if self.outbound_high_water < self.max_packet_size {
+            return Err(format!("outbound_high_water must be at least max_packet_size, {}", self.outbound_high_water).into());
+        }
+
+        if u32::try_from(self.reconnect_hint.as_secs()).is_err() {
+            return Err(format!("reconnect_hint is too long, {}", self.reconnect_hint.as_secs()).into());
+        }
+
+        if self.view_radius <= 0 {
+            return Err(format!("view_radius must be positive, {}", self.view_radius).into());
+        }
+
+        if self.max_waitings == 0 {
+            return Err("max_waitings must be positive".into());
+        }
+
+        let durations = [
+            ("movement_tick", self.movement_tick),
+            ("autosave_interval", self.autosave_interval),
+            ("handshake_timeout", self.handshake_timeout),
+            ("bans_reload_interval", self.bans_reload_interval),
+        ];
+
+        for (key, duration) in durations {
+            if duration.is_zero() {
+                return Err(format!("{key} must be positive").into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// Raw settings of the file, taken out one by one so the ones
+/// left over can be told apart as unknown.
+/// 
+struct Settings {
+    table: toml::value::Table,
+    environment: bool,
+}
+
+impl Settings {
+    fn open() -> Result<Self, Box<dyn Error>> {
+        let (path, is_default) = match std::env::var("CONFIG_PATH") {
+            Ok(path) => (path, false),
+            Err(_) => (String::from("config.toml"), true),
+        };
+
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && is_default => String::new(),
+            Err(e) => return Err(format!("failed to read config {path:?}, {e}").into()),
+        };
+
+        Settings::parse(&source, &path, true)
+    }
+
+    ///
+    /// `environment` tells whether variables win over `source`.
+    /// 
+    fn parse(source: &str, path: &str, environment: bool) -> Result<Self, Box<dyn Error>> {
+        let table = match toml::from_str(source) {
+            Ok(table) => table,
+            Err(e) => return Err(format!("invalid config {path:?}, {e}").into()),
+        };
+
+        Ok(Settings { table, environment })
+    }
+
+    fn get<T>(&mut self, key: &str) -> Result<Option<T>, Box<dyn Error>> where T: FromStr, T::Err: std::fmt::Display {
+        let file = self.table.remove(key);
+
+        let variable = match self.environment {
+            true => std::env::var(format!("{ENVIRONMENT_PREFIX}{}", key.to_uppercase())).ok().or_else(|| {
+                LEGACY_VARIABLES.iter().find(|(name, _)| *name == key).and_then(|(_, variable)| std::env::var(variable).ok())
+            }),
+            false => None,
+        };
+
+        let value = match variable {
+            Some(value) => value,
+            None => match file {
+                Some(toml::Value::String(value)) => value,
+                Some(toml::Value::Integer(value)) => value.to_string(),
+                Some(value) => return Err(format!("invalid {key}, expected a string or an integer, {value}").into()),
+                None => return Ok(None),
+            },
+        };
+
+        match value.parse() {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => Err(format!("invalid {key}, {e}").into()),
+        }
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self.table.keys().next() {
+            Some(key) => Err(format!("unknown setting, {key:?}").into()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        match Constants::from_toml(source) {
+            Ok(_) => panic!("read, {source:?}"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn missing_keys_take_their_defaults() {
+        let constants = Constants::from_toml("auth_secret = \"secret\"").unwrap();
+
+        assert_eq!(constants.view_radius, 10);
+
+        assert_eq!(constants.duplicate_session, DuplicateSession::Replace);
+
+        assert_eq!(constants.auth_keys.active().id, "default");
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert_eq!(error("auth_secret = \"secret\"\nview_raduis = 3"), "unknown setting, \"view_raduis\"");
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert_eq!(error(""), "missing auth_secret or auth_keys");
+
+        assert_eq!(error("auth_secret = \"secret\"\nview_radius = \"far\""), "invalid view_radius, invalid digit found in string");
+
+        assert_eq!(error("auth_secret = \"secret\"\nview_radius = [1]"), "invalid view_radius, expected a string or an integer, [1]");
+
+        assert_eq!(error("auth_secret = \"secret\"\nduplicate_session = \"both\""), "invalid duplicate_session, unknown duplicate session policy, \"both\"");
+
+        assert_eq!(error("auth_secret = \"secret\"\nview_radius = 0"), "view_radius must be positive, 0");
+
+        assert_eq!(error("auth_secret = \"secret\"\nmovement_tick = 0"), "movement_tick must be positive");
+
+        assert!(error("auth_secret = ").starts_with("invalid config \"source\""));
+    }
+}
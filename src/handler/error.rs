@@ -0,0 +1,131 @@
+use std::{fmt, io};
+
+use crate::{auth::{self, Roles}, common::Bytes, net};
+
+///
+/// A request that makes no sense in the state of the game.
+/// 
+#[derive(Debug)]
+pub enum GameError {
+    ConnectionNotFound([u8; 16]),
+    NotAuthenticated,
+    AlreadyAuthenticated([u8; 16]),
+    Forbidden { id: [u8; 16], required: Roles },
+    Banned { id: [u8; 16], reason: String },
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::ConnectionNotFound(id) => write!(f, "connection not found, {}", id.to_hex()),
+            GameError::NotAuthenticated => write!(f, "packet arrived before hello"),
+            GameError::AlreadyAuthenticated(id) => write!(f, "hello arrived again from {}", id.to_hex()),
+            GameError::Forbidden { id, required } => write!(f, "{} is not {required}", id.to_hex()),
+            GameError::Banned { id, reason } => write!(f, "{} is banned, {reason}", id.to_hex()),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+///
+/// Any failure of a job.
+/// 
+#[derive(Debug)]
+pub enum Error {
+    Net(net::Error),
+    Auth(auth::Error),
+    Storage(io::Error),
+    Game(GameError),
+}
+
+///
+/// What to do with the client a failed job was about.
+/// 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Action {
+    Drop,
+    Warn,
+    Ignore,
+}
+
+impl Error {
+    pub fn action(&self) -> Action {
+        match self {
+            Error::Net(net::Error::TooLarge(_)) => Action::Warn,
+            Error::Net(_) => Action::Drop,
+            Error::Auth(_) => Action::Drop,
+            Error::Storage(_) => Action::Warn,
+            Error::Game(GameError::ConnectionNotFound(_)) => Action::Ignore,
+            Error::Game(GameError::NotAuthenticated) => Action::Drop,
+            Error::Game(GameError::AlreadyAuthenticated(_)) => Action::Warn,
+            Error::Game(GameError::Forbidden { .. }) => Action::Warn,
+            Error::Game(GameError::Banned { .. }) => Action::Drop,
+        }
+    }
+}
+
+impl Error {
+    ///
+    /// Short name of what went wrong, for metrics.
+    /// 
+    pub fn label(&self) -> &'static str {
+        match self {
+            Error::Net(net::Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => "closed",
+            Error::Net(net::Error::Io(_)) => "io",
+            Error::Net(net::Error::Decode(_)) => "decode",
+            Error::Net(net::Error::TooLarge(_)) => "too_large",
+            Error::Net(net::Error::Overflow(_)) => "overflow",
+            Error::Auth(auth::Error::Malformed(_)) => "malformed_token",
+            Error::Auth(auth::Error::UnknownKey(_)) => "unknown_key",
+            Error::Auth(auth::Error::InvalidKey { .. }) => "invalid_key",
+            Error::Auth(auth::Error::RetiredKey(_)) => "retired_key",
+            Error::Auth(auth::Error::InvalidSignature) => "invalid_signature",
+            Error::Auth(auth::Error::Expired { .. }) => "token_expired",
+            Error::Auth(auth::Error::IssuedInFuture { .. }) => "token_from_future",
+            Error::Storage(_) => "storage",
+            Error::Game(GameError::ConnectionNotFound(_)) => "connection_not_found",
+            Error::Game(GameError::NotAuthenticated) => "not_authenticated",
+            Error::Game(GameError::AlreadyAuthenticated(_)) => "already_authenticated",
+            Error::Game(GameError::Forbidden { .. }) => "forbidden",
+            Error::Game(GameError::Banned { .. }) => "banned",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Net(e) => write!(f, "{e}"),
+            Error::Auth(e) => write!(f, "{e}"),
+            Error::Storage(e) => write!(f, "storage failed, {e}"),
+            Error::Game(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<net::Error> for Error {
+    fn from(e: net::Error) -> Self {
+        Error::Net(e)
+    }
+}
+
+impl From<net::DecodeError> for Error {
+    fn from(e: net::DecodeError) -> Self {
+        Error::Net(net::Error::Decode(e))
+    }
+}
+
+impl From<auth::Error> for Error {
+    fn from(e: auth::Error) -> Self {
+        Error::Auth(e)
+    }
+}
+
+impl From<GameError> for Error {
+    fn from(e: GameError) -> Self {
+        Error::Game(e)
+    }
+}
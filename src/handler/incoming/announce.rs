@@ -0,0 +1,21 @@
+use crate::{auth::Roles, handler::{Context, error::Error}, net::packet};
+
+///
+/// Handle a notice a moderator sends to everyone.
+/// 
+/// Players without the role are refused, and the packet is
+/// logged as forbidden.
+/// 
+pub fn handle(message: String, key: [u8; 16], context: &mut Context) -> Result<(), Error> {
+    context.authorize(&key, Roles::MODERATOR)?;
+
+    let notice = packet::Outgoing::Notice { message }.serialize();
+
+    let ids = context.connections.keys().copied().collect::<Vec<_>>();
+
+    for id in ids.iter() {
+        context.send(id, &notice);
+    }
+
+    Ok(())
+}
@@ -1,16 +1,16 @@
-use std::error::Error;
-
 use crate::net::packet;
 
-use super::Context;
+use super::{Context, error::{Error, GameError}};
 
 mod ping;
 mod movement;
+mod announce;
 
-pub fn handle(packet: packet::Incoming, key: [u8; 16], context: &mut Context) -> Result<(), Box<dyn Error>> {
+pub fn handle(packet: packet::Incoming, key: [u8; 16], context: &mut Context) -> Result<(), Error> {
     match packet {
         packet::Incoming::Ping { timestamp } => ping::handle(timestamp, key, context),
         packet::Incoming::Move { direction } => movement::handle(direction, key, context),
-        _ => Ok(())
+        packet::Incoming::Announce { message } => announce::handle(message, key, context),
+        packet::Incoming::Hello { .. } => Err(GameError::AlreadyAuthenticated(key).into()),
     }
 }
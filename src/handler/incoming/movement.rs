@@ -1,7 +1,6 @@
-use std::error::Error;
 use tokio::time;
 
-use crate::{handler::Context, job::{Schedule, Job}, map::object::{Object, HumanState}};
+use crate::{handler::{Context, error::{Error, GameError}}, job::{Schedule, Job}, map::object::{Object, HumanState}};
 
 ///
 /// Handle the request for move.
@@ -9,13 +8,13 @@ use crate::{handler::Context, job::{Schedule, Job}, map::object::{Object, HumanS
 /// Change the state of the human object, and
 /// let a job execute the actual position swtiching.
 /// 
-pub fn handle(direction: u8, key: [u8; 16], context: &mut Context) -> Result<(), Box<dyn Error>> {
-    let (_, position) = match context.connections.get(&key) {
+pub fn handle(direction: u8, key: [u8; 16], context: &mut Context) -> Result<(), Error> {
+    let (_, position, _) = match context.connections.get(&key) {
         Some(conn) => conn,
-        None => return Err("connectio not found".into())
+        None => return Err(GameError::ConnectionNotFound(key).into())
     };
     
-    if let Some(tile) = context.map.get_mut(&position) {
+    if let Some(tile) = context.map.get_mut(position) {
         if let Some(Object::Human { state, .. }) = &mut tile.object {
             if direction == 0 {
                 *state = HumanState::Idle { updated_at: *match state {
@@ -31,7 +30,7 @@ pub fn handle(direction: u8, key: [u8; 16], context: &mut Context) -> Result<(),
                     return Ok(());
                 };
 
-                let tick = time::Duration::from_millis(300);
+                let tick = context.constants.movement_tick;
 
                 let now = time::Instant::now();
 
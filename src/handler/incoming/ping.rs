@@ -1,21 +1,19 @@
-use std::error::Error;
-
-use crate::{handler::Context, net::{packet, io::Writer}};
+use crate::{handler::{Context, error::{Error, GameError}}, net::packet};
 
 ///
 /// Handle the request for ping.
 /// 
 /// Just return the passed timestamp to the connection.
 /// 
-pub fn handle(timestamp: i64, key: [u8; 16], context: &mut Context) -> Result<(), Box<dyn Error>> {
-    let stream = match context.connections.get(&key) {
-        Some((stream, _)) => stream,
-        None => return Ok(())
+pub fn handle(timestamp: i64, key: [u8; 16], context: &mut Context) -> Result<(), Error> {
+    let stream = match context.connections.get_mut(&key) {
+        Some((stream, _, _)) => stream,
+        None => return Err(GameError::ConnectionNotFound(key).into())
     };
     
     let outgoing = packet::Outgoing::Pong { timestamp };
 
-    stream.try_write_one(&mut outgoing.serialize())?;
+    stream.send(&outgoing.serialize())?;
 
     Ok(())
 }
@@ -1,10 +1,30 @@
-use std::error::Error;
-use tokio::net::TcpStream;
+use tokio::{net::TcpStream, time};
 
-use crate::handler::Context;
+use crate::{handler::{Context, error::Error}, net::Connection, job::{Schedule, Job}};
 
-pub fn handle(stream: TcpStream, context: &mut Context) -> Result<(), Box<dyn Error>> {
-    context.waitings.push(stream);
+///
+/// Keep an accepted socket waiting for its hello, until the handshake deadline.
+/// 
+/// Sockets beyond the limit of waiting ones are closed right away.
+/// 
+pub fn handle(stream: TcpStream, context: &mut Context) -> Result<(), Error> {
+    if context.waitings.len() >= context.constants.max_waitings {
+        tracing::warn!(waitings = context.waitings.len(), "too many waiting connections");
+
+        context.metrics.dropped("too_many_waitings");
+
+        return Ok(());
+    }
+
+    context.waiting_serial += 1;
+
+    let handle = context.waiting_serial;
+
+    context.waitings.insert(handle, Connection::spawn(stream, handle, context.constants.outbound_high_water, context.constants.max_packet_size, context.metrics.clone(), context.inbound_sender.clone()));
+
+    tracing::debug!(waiting = handle, "accepted");
+
+    context.schedule_queue.push(Schedule::new(Job::Evict(handle), time::Instant::now() + context.constants.handshake_timeout));
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+
+use tokio::time;
+
+use crate::{admin::{Command, Request}, auth::Ban, common::{Bytes, math::Vector3}, handler::{Context, error::Error}, map::object::{Object, HumanState}, net::packet};
+
+///
+/// Run a command of an operator and answer it.
+///
+/// Failures are answered rather than returned, since they
+/// are about the command and not about any client.
+///
+pub fn handle(request: Request, context: &mut Context) -> Result<(), Error> {
+    let Request { command, reply } = request;
+
+    let answer = match command {
+        Command::List => list(context),
+        Command::Kick { id } => kick(id, context),
+        Command::Ban { id, until, reason } => match context.ban(id, Ban { until, reason }) {
+            Ok(()) => String::from("banned"),
+            Err(e) => format!("error: {e}"),
+        },
+        Command::Broadcast { message } => broadcast(message, context),
+        Command::Teleport { id, to } => teleport(id, to, context),
+        Command::Stats => stats(context),
+    };
+
+    // The operator may have hung up already.
+    let _ = reply.send(answer);
+
+    Ok(())
+}
+
+fn list(context: &Context) -> String {
+    let mut lines = context.connections.iter()
+        .map(|(id, (_, position, roles))| format!("{} {} {} {} {roles}", id.to_hex(), position.x, position.y, position.z))
+        .collect::<Vec<_>>();
+
+    lines.sort();
+
+    lines.push(format!("{} connections", context.connections.len()));
+
+    lines.join("\n")
+}
+
+fn kick(id: [u8; 16], context: &mut Context) -> String {
+    let handle = match context.connections.get(&id) {
+        Some((stream, _, _)) => stream.handle(),
+        None => return format!("error: not connected, {}", id.to_hex()),
+    };
+
+    context.metrics.dropped("admin");
+
+    context.send(&id, &packet::Outgoing::Kick { reason: packet::Reason::Admin }.serialize());
+
+    Context::schedule_drop(&mut context.schedule_queue, handle);
+
+    String::from("kicked")
+}
+
+fn broadcast(message: String, context: &mut Context) -> String {
+    let notice = packet::Outgoing::Notice { message }.serialize();
+
+    let ids = context.connections.keys().copied().collect::<Vec<_>>();
+
+    for id in ids.iter() {
+        context.send(id, &notice);
+    }
+
+    format!("sent to {}", ids.len())
+}
+
+fn teleport(id: [u8; 16], to: Vector3, context: &mut Context) -> String {
+    let from = match context.connections.get(&id) {
+        Some((_, position, _)) => *position,
+        None => return format!("error: not connected, {}", id.to_hex()),
+    };
+
+    if from == to {
+        return String::from("teleported");
+    }
+
+    let is_passable = match (context.map.get(&from).and_then(|tile| tile.object.as_ref()), context.map.get(&to)) {
+        (Some(object), Some(tile)) => tile.is_passable_by(object),
+        _ => false,
+    };
+
+    if !is_passable {
+        return format!("error: cannot stand at {to}");
+    }
+
+    let mut object = context.map.get_mut(&from).and_then(|tile| tile.object.take());
+
+    // A move under way would go on from the old position.
+    if let Some(Object::Human { state, .. }) = &mut object {
+        *state = HumanState::Idle { updated_at: *match state {
+            HumanState::Idle { updated_at } => updated_at,
+            HumanState::Move { updated_at, .. } => updated_at,
+        }};
+    }
+
+    if let Some(tile) = context.map.get_mut(&to) {
+        tile.object = object;
+    }
+
+    if let Some(conn) = context.connections.get_mut(&id) {
+        conn.1 = to;
+    }
+
+    let outgoing = packet::Outgoing::Arrive { id, x: to.x, y: to.y, z: to.z }.serialize();
+
+    context.relocate(id, &from, to, &outgoing);
+
+    String::from("teleported")
+}
+
+fn stats(context: &Context) -> String {
+    let now = time::Instant::now();
+
+    let mut kinds: BTreeMap<&'static str, (usize, time::Instant)> = BTreeMap::new();
+
+    for schedule in context.schedule_queue.iter() {
+        let (count, next) = kinds.entry(schedule.job.kind()).or_insert((0, schedule.deadline));
+
+        *count += 1;
+
+        *next = (*next).min(schedule.deadline);
+    }
+
+    let mut lines = vec![
+        format!("connections {}", context.connections.len()),
+        format!("waitings {}", context.waitings.len()),
+        format!("scheduled {}", context.schedule_queue.len()),
+    ];
+
+    for (kind, (count, next)) in kinds {
+        lines.push(format!("  {kind} {count}, next in {}ms", next.saturating_duration_since(now).as_millis()));
+    }
+
+    if context.shutdown_at.is_some() {
+        lines.push(String::from("shutting down"));
+    }
+
+    lines.join("\n")
+}
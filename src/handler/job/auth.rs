@@ -1,56 +1,58 @@
-use std::{error::Error, io};
-
-use crate::{handler::Context, net::{io::*, packet}, auth, job::{Schedule, Job}};
-
-pub fn handle(index: usize, context: &mut Context) -> Result<(), Box<dyn Error>> {
-    let stream = match context.waitings.get(index) {
+use crate::{handler::{Context, error::{Error, GameError}}, net::{packet, Event}, auth, job::{Schedule, Job}};
+
+///
+/// Authenticate a waiting connection by its hello.
+/// 
+/// The connection closing or failing before is an error as well.
+/// 
+/// On failure the connection is left in place for the dispatcher to remove.
+/// 
+pub fn handle(handle: u64, event: Event, context: &mut Context) -> Result<(), Error> {
+    let stream = match context.waitings.get_mut(&handle) {
         Some(stream) => stream,
         None => return Ok(())
     };
 
-    let mut buf = vec![0 as u8; 2];
-
-    if let Err(e) = stream.try_read_one(&mut buf) {
-        if e.kind() != io::ErrorKind::WouldBlock {
-            eprintln!("{e}");
-
-            context.waitings.remove(index);
-
-            return Ok(());
-        }
-    
-        return Ok(());
-    }
-
-    let token = match packet::Incoming::deserialize(&mut buf) {
-        Ok(packet::Incoming::Hello { token }) => token,
-        Ok(_) => {
-            eprintln!("auth interrupted");
+    let buf = event.into_frame()?;
 
-            context.waitings.remove(index);
+    let token = match packet::Incoming::deserialize(&buf)? {
+        packet::Incoming::Hello { token } => token,
+        _ => return Err(GameError::NotAuthenticated.into()),
+    };
 
-            return Ok(());
-        }
+    let token = match auth::verify(&token, &context.constants.auth_keys, context.constants.token_max_age, context.constants.token_clock_skew) {
+        Ok(token) => token,
         Err(e) => {
-            eprintln!("{e}");
+            let reason = match e {
+                auth::Error::Expired { .. } => packet::Reason::TokenExpired,
+                auth::Error::IssuedInFuture { .. } => packet::Reason::TokenFromFuture,
+                _ => packet::Reason::InvalidToken,
+            };
 
-            context.waitings.remove(index);
+            stream.send(&packet::Outgoing::Refuse { reason }.serialize())?;
 
-            return Ok(());
+            return Err(e.into());
         }
     };
 
-    let token = auth::verify(&token, &context.constants.auth_secret)?;
+    if let Some(ban) = context.bans.find(&token.id, auth::now_millis()) {
+        stream.send(&packet::Outgoing::Refuse { reason: packet::Reason::Banned }.serialize())?;
+
+        return Err(GameError::Banned { id: token.id, reason: ban.reason.clone() }.into());
+    }
 
     let outgoing = packet::Outgoing::Hello { id: token.id };
 
-    stream.try_write_one(&mut outgoing.serialize())?;
+    stream.send(&outgoing.serialize())?;
 
-    let stream = context.waitings.remove(index);
+    let stream = match context.waitings.remove(&handle) {
+        Some(stream) => stream,
+        None => return Ok(())
+    };
 
-    let schedule = Schedule::now(Job::Welcome(stream, token.id));
+    let schedule = Schedule::now(Job::Welcome(stream, token));
 
     context.schedule_queue.push(schedule);
     
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,14 @@
+use tokio::time;
+
+use crate::{handler::{Context, error::Error}, job::{Schedule, Job}};
+
+///
+/// Save every connected character, and come back after the interval.
+/// 
+pub fn handle(context: &mut Context) -> Result<(), Error> {
+    let job = Job::Autosave;
+
+    context.schedule_queue.push(Schedule::new(job, time::Instant::now() + context.constants.autosave_interval));
+
+    context.save_all()
+}
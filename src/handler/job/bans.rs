@@ -0,0 +1,21 @@
+use tokio::time;
+
+use crate::{handler::{Context, error::Error}, job::{Schedule, Job}};
+
+///
+/// Read the ban list again if its file changed, kicking whoever
+/// got banned, and come back after the interval.
+/// 
+pub fn handle(context: &mut Context) -> Result<(), Error> {
+    let job = Job::ReloadBans;
+
+    context.schedule_queue.push(Schedule::new(job, time::Instant::now() + context.constants.bans_reload_interval));
+
+    if context.bans.reload().map_err(Error::Storage)? {
+        tracing::info!("ban list reloaded");
+
+        context.kick_banned();
+    }
+
+    Ok(())
+}
@@ -1,11 +1,18 @@
-use std::error::Error;
-use crate::{handler::Context, net::{packet, io::Writer}, map::object::Object};
+use crate::{handler::{Context, error::Error}, net::packet, map::object::Object, storage::Character};
 
 ///
 /// Drop a connection
 /// 
-pub fn handle(id: [u8; 16], context: &mut Context) -> Result<(), Box<dyn Error>> {
-    if let Some((_, position)) = context.connections.remove(&id) {
+/// It is gone already when the session ended before,
+/// or another connection took its place.
+/// 
+pub fn handle(handle: u64, context: &mut Context) -> Result<(), Error> {
+    let id = match context.sessions.remove(&handle) {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+
+    if let Some((_, position, _)) = context.connections.remove(&id) {
         if let Some(tile) = context.map.get_mut(&position) {
             if let Some(Object::Human { id: object_id, .. }) = &tile.object {
                 if id == *object_id {
@@ -14,15 +21,15 @@ pub fn handle(id: [u8; 16], context: &mut Context) -> Result<(), Box<dyn Error>>
             }
         }
 
-        let mut outgoing = packet::Outgoing::Disconnect { id }.serialize();
+        context.grid.remove(&id, &position);
 
-        for (key, (stream, _)) in context.connections.iter() {
-            if let Err(e) = stream.try_write_one(&mut outgoing) {
-                eprintln!("{e}");
+        tracing::info!(%position, "dropped");
 
-                Context::schedule_drop(&mut context.schedule_queue, *key);
-            }
-        }
+        let outgoing = packet::Outgoing::Disconnect { id }.serialize();
+
+        context.broadcast(&position, &outgoing);
+
+        context.storage.save(id, Character { position }).map_err(Error::Storage)?;
     }
 
     Ok(())
@@ -0,0 +1,16 @@
+use crate::handler::{Context, error::Error};
+
+///
+/// Close a waiting connection that missed the handshake deadline.
+/// 
+/// It is gone already when it did authenticate or fail in time.
+/// 
+pub fn handle(handle: u64, context: &mut Context) -> Result<(), Error> {
+    if context.waitings.remove(&handle).is_some() {
+        tracing::info!("handshake timed out");
+
+        context.metrics.dropped("handshake_timeout");
+    }
+
+    Ok(())
+}
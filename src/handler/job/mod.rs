@@ -1,26 +1,90 @@
-use std::error::Error;
+use tracing::field;
 
-use crate::job::Job;
+use crate::{job::Job, common::Bytes};
 
-use super::Context;
+use super::{Context, error::{Error, Action}};
 
 mod accept;
 mod auth;
 mod welcome;
 mod drop;
 mod read;
+mod autosave;
+mod evict;
 mod movement;
+mod bans;
+mod shutdown;
+mod admin;
 
 ///
-/// Handle a job.
+/// The client a job is about, to be dropped when the job fails.
 /// 
-pub fn handle(context: &mut Context, job: Job) -> Result<(), Box<dyn Error>> {
-    match job {
+enum Subject {
+    Waiting(u64),
+    Connection([u8; 16]),
+}
+
+///
+/// Handle a job, in a span of its kind and of the client it is about.
+/// 
+pub fn handle(context: &mut Context, job: Job) {
+    let subject = match &job {
+        Job::Auth(handle, _) => Some(Subject::Waiting(*handle)),
+        Job::Read(key, _) => Some(Subject::Connection(*key)),
+        _ => None,
+    };
+
+    let span = tracing::info_span!("job", kind = job.kind(), connection = field::Empty, waiting = field::Empty);
+
+    match &job {
+        Job::Auth(handle, _) | Job::Evict(handle) => { span.record("waiting", handle); },
+        Job::Read(key, _) => { span.record("connection", field::display(key.to_hex())); },
+        Job::Drop(handle) => if let Some(key) = context.sessions.get(handle) { span.record("connection", field::display(key.to_hex())); },
+        Job::Welcome(_, token) => { span.record("connection", field::display(token.id.to_hex())); },
+        _ => {},
+    }
+
+    let _entered = span.enter();
+
+    context.metrics.job(job.kind());
+
+    let result = match job {
         Job::Accept(stream) => accept::handle(stream, context),
-        Job::Auth(index) => auth::handle(index, context),
-        Job::Welcome(stream, key) => welcome::handle(key, stream, context),
-        Job::Drop(key) => drop::handle(key, context),
-        Job::Read(key) => read::handle(key, context),
+        Job::Auth(handle, event) => auth::handle(handle, event, context),
+        Job::Welcome(stream, token) => welcome::handle(token, stream, context),
+        Job::Drop(handle) => drop::handle(handle, context),
+        Job::Read(key, event) => read::handle(key, event, context),
         Job::Move { from, tick } => movement::handle(from, tick, context),
+        Job::Autosave => autosave::handle(context),
+        Job::Evict(handle) => evict::handle(handle, context),
+        Job::ReloadBans => bans::handle(context),
+        Job::Shutdown => shutdown::handle(context),
+        Job::Admin(request) => admin::handle(request, context),
+    };
+
+    if let Err(e) = result {
+        fail(context, subject, e);
+    }
+}
+
+fn fail(context: &mut Context, subject: Option<Subject>, e: Error) {
+    let action = e.action();
+
+    match action {
+        Action::Ignore => return,
+        Action::Drop => tracing::info!(error = %e, "dropping client"),
+        Action::Warn => tracing::warn!(error = %e, "job failed"),
+    }
+
+    if action == Action::Drop {
+        context.metrics.dropped(e.label());
+
+        match subject {
+            Some(Subject::Waiting(handle)) => { context.waitings.remove(&handle); },
+            Some(Subject::Connection(key)) => if let Some((stream, _, _)) = context.connections.get(&key) {
+                Context::schedule_drop(&mut context.schedule_queue, stream.handle());
+            },
+            None => {},
+        }
     }
 }
@@ -1,42 +1,19 @@
-use std::error::Error;
-use std::io;
-
-use crate::{handler::{Context, incoming}, net::{packet, io::Reader}};
+use crate::{handler::{Context, incoming, error::Error}, net::{packet, Event}};
 
 ///
-/// Read from a connection
+/// Handle what the reader of a connection passed on.
 /// 
-pub fn handle(key: [u8; 16], context: &mut Context) -> Result<(), Box<dyn Error>> {
-    if let Some((stream, _)) = context.connections.get(&key) {
-        let mut buf = vec![0 as u8; 2];
-
-        if let Err(e) = stream.try_read_one(&mut buf) {
-            if e.kind() != io::ErrorKind::WouldBlock {
-                eprintln!("{e}");
-
-                Context::schedule_drop(&mut context.schedule_queue, key);
-            }
-        
-            return Ok(());
-        }
-
-        let packet = match packet::Incoming::deserialize(&mut buf) {
-            Ok(packet) => packet,
-            Err(e) => {
-                eprintln!("{e}");
-
-                Context::schedule_drop(&mut context.schedule_queue, key);
-
-                return Ok(());
-            }
-        };
+/// Frames are handled in the order they arrived,
+/// the end of the connection is returned as an error.
+/// 
+pub fn handle(key: [u8; 16], event: Event, context: &mut Context) -> Result<(), Error> {
+    if !context.connections.contains_key(&key) {
+        return Ok(())
+    }
 
-        if let Err(e) = incoming::handle(packet, key, context) {
-            eprintln!("{e}");
+    let buf = event.into_frame()?;
 
-            Context::schedule_drop(&mut context.schedule_queue, key);
-        }
-    }
+    let packet = packet::Incoming::deserialize(&buf)?;
 
-    Ok(())
+    incoming::handle(packet, key, context)
 }
@@ -0,0 +1,36 @@
+use tokio::time;
+
+use crate::{handler::{Context, error::Error}, net::packet};
+
+///
+/// Start shutting down.
+/// 
+/// No socket is accepted nor read anymore, waiting ones are closed,
+/// and players are told when to come back. The loop keeps handling
+/// what is already due until the grace period is over.
+/// 
+pub fn handle(context: &mut Context) -> Result<(), Error> {
+    if context.shutdown_at.is_some() {
+        return Ok(());
+    }
+
+    tracing::info!(connections = context.connections.len(), "shutting down");
+
+    context.shutdown_at = Some(time::Instant::now() + context.constants.shutdown_grace_period);
+
+    context.listener = None;
+
+    context.waitings.clear();
+
+    let reconnect_after = u32::try_from(context.constants.reconnect_hint.as_secs()).unwrap_or(u32::MAX);
+
+    let notice = packet::Outgoing::Shutdown { reason: packet::Reason::Shutdown, reconnect_after }.serialize();
+
+    let ids = context.connections.keys().copied().collect::<Vec<_>>();
+
+    for id in ids {
+        context.send(&id, &notice);
+    }
+
+    Ok(())
+}
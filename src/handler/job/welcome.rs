@@ -1,47 +1,92 @@
-use std::error::Error;
-use tokio::net::TcpStream;
+use crate::{net::{packet, Connection}, handler::{Context, error::Error}, map::object::Object, constants::DuplicateSession, auth::Token};
 
-use crate::{net::{packet, io::Writer}, handler::Context, map::object::Object};
+use super::drop;
 
 /// 
 /// Welcome a conection.
 /// 
-pub fn handle(id: [u8; 16], stream: TcpStream, context: &mut Context) -> Result<(), Box<dyn Error>> {
-    for (current, tile) in context.map.iter_mut() {
-        if let None = tile.object {
-            tile.object = Some(Object::new_human(id));
-            
-            let mut users = vec![(id, current.x, current.y, current.z)];
+pub fn handle(token: Token, mut stream: Connection, context: &mut Context) -> Result<(), Error> {
+    let Token { id, roles, .. } = token;
 
-            let mut connect = packet::Outgoing::Connect { id, x: current.x, y: current.y, z: current.z }.serialize();
+    if context.connections.contains_key(&id) {
+        match context.constants.duplicate_session {
+            DuplicateSession::Refuse => {
+                context.metrics.dropped("duplicate_session");
 
-            for (id, (stream, position)) in context.connections.iter() {
-                if let Err(e) = stream.try_write_one(&mut connect) {
-                    eprintln!("{e}");
+                stream.send(&packet::Outgoing::Refuse { reason: packet::Reason::DuplicateSession }.serialize())?;
 
-                    Context::schedule_drop(&mut context.schedule_queue, *id);
+                return Ok(());
+            },
+            DuplicateSession::Replace => {
+                context.send(&id, &packet::Outgoing::Kick { reason: packet::Reason::DuplicateSession }.serialize());
+
+                tracing::info!("replacing the live session");
+
+                context.metrics.dropped("duplicate_session");
 
-                    continue;
+                let handle = context.connections[&id].0.handle();
+
+                if let Err(e) = drop::handle(handle, context) {
+                    tracing::warn!(error = %e, "failed to drop the live session");
                 }
+            },
+        }
+    }
 
-                users.push((id.clone(), position.x, position.y, position.z));
-            }
+    let human = Object::new_human(id);
 
-            let mut introduce = packet::Outgoing::Introduce { users }.serialize();
+    let saved = context.storage.load(&id)
+        .unwrap_or_else(|e| {
+            tracing::warn!(error = %Error::Storage(e), "failed to load character");
 
-            if let Err(e) = stream.try_write_one(&mut introduce) {
-                eprintln!("{e}");
+            None
+        })
+        .map(|character| character.position)
+        .filter(|position| context.map.get(position).map(|tile| tile.is_passable_by(&human)).unwrap_or(false));
 
-                Context::schedule_drop(&mut context.schedule_queue, id);
+    let current = match saved.or_else(|| context.spawner.pick(&context.spawn_region, &human, &context.map, &context.grid)) {
+        Some(current) => current,
+        None => {
+            tracing::warn!(region = %context.spawn_region, "no room to spawn");
 
-                return Ok(());
-            }
+            context.metrics.dropped("spawn_full");
+
+            let refuse = packet::Outgoing::Refuse { reason: packet::Reason::SpawnFull }.serialize();
+
+            stream.send(&refuse)?;
 
-            context.connections.insert(id, (stream, current.clone()));
-            
-            return Ok(());
+            return Ok(())
         }
+    };
+
+    let nearby = context.grid.nearby(&current);
+
+    let mut users = vec![(id, current.x, current.y, current.z)];
+
+    users.extend(nearby.iter().map(|(other, position)| (*other, position.x, position.y, position.z)));
+
+    stream.send(&packet::Outgoing::Introduce { users }.serialize())?;
+
+    for terrain in context.terrain.iter() {
+        stream.send(terrain)?;
+    }
+
+    let connect = packet::Outgoing::Connect { id, x: current.x, y: current.y, z: current.z }.serialize();
+
+    for (other, _) in nearby {
+        context.send(&other, &connect);
     }
 
+    if let Some(tile) = context.map.get_mut(&current) {
+        tile.object = Some(human);
+    }
+
+    context.grid.insert(id, current);
+
+    context.sessions.insert(stream.handle(), id);
+
+    context.connections.insert(id, (stream, current, roles));
+
+    tracing::info!(position = %current, %roles, "welcomed");
     Ok(())
 }
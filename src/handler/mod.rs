@@ -1,53 +1,398 @@
 mod incoming;
 mod job;
 mod selector;
+mod error;
 
-use std::error::Error;
-use std::collections::{BinaryHeap, HashMap};
+pub use error::{Error, GameError, Action};
 
-use tokio::net::{TcpStream, TcpListener};
+use std::{collections::{BinaryHeap, HashMap}, io, sync::Arc};
 
-use crate::common::math::Vector3;
+use tokio::{net::TcpListener, sync::mpsc, time};
+
+use crate::admin::Request;
+use crate::auth::{self, Ban, BanList, Roles};
+use crate::common::{Bytes, math::Vector3};
 use crate::constants::Constants;
 use crate::job::{Schedule, Job};
-use crate::map::tile::Tile;
+use crate::map::{tile::Tile, grid::Grid, loader::Map, spawn::Spawner};
+use crate::metrics::Metrics;
+use crate::net::{self, Connection, Inbound, packet};
+use crate::storage::{Character, Storage};
 
 pub struct Context {
     constants: Constants,
     schedule_queue: BinaryHeap<Schedule<Job>>,
-    listener: TcpListener,
-    waitings: Vec<TcpStream>,
-    connections: HashMap<[u8; 16], (TcpStream, Vector3)>,
+    listener: Option<TcpListener>,
+    shutdown: mpsc::Receiver<()>,
+    shutdown_sender: mpsc::Sender<()>,
+    shutdown_at: Option<time::Instant>,
+    waitings: HashMap<u64, Connection>,
+    waiting_serial: u64,
+    connections: HashMap<[u8; 16], (Connection, Vector3, Roles)>,
+    sessions: HashMap<u64, [u8; 16]>,
+    inbound: mpsc::Receiver<Inbound>,
+    inbound_sender: mpsc::Sender<Inbound>,
     map: HashMap<Vector3, Tile>,
+    grid: Grid,
+    terrain: Vec<Vec<u8>>,
+    spawner: Spawner,
+    spawn_region: String,
+    storage: Box<dyn Storage>,
+    bans: BanList,
+    metrics: Arc<Metrics>,
+    admin: mpsc::Receiver<Request>,
+    admin_sender: mpsc::Sender<Request>,
 }
 
 impl Context {
-    pub fn new(constants: Constants, map: HashMap<Vector3, Tile>, listener: TcpListener) -> Self {
-        Context {
+    pub fn new(constants: Constants, map: Map, storage: Box<dyn Storage>, bans: BanList, listener: TcpListener) -> Result<Self, Box<dyn std::error::Error>> {
+        let grid = Grid::new(constants.view_radius);
+
+        let terrain = serialize_terrain(&map.tiles);
+
+        // Every welcome queues the whole terrain at once, framed.
+        let welcome = terrain.iter().map(|packet| 2 + packet.len()).sum::<usize>();
+
+        if welcome > constants.outbound_high_water {
+            return Err(format!("terrain of the map takes {welcome} bytes, more than outbound_high_water, {}", constants.outbound_high_water).into());
+        }
+
+        let spawner = Spawner::new(constants.spawn_policy, map.regions);
+
+        let spawn_region = match &constants.spawn_region {
+            Some(name) if !spawner.contains(name) => return Err(format!("unknown spawn region, {name:?}").into()),
+            Some(name) => name.clone(),
+            None => spawner.default_region().to_owned(),
+        };
+
+        let (admin_sender, admin) = mpsc::channel(ADMIN_QUEUE);
+
+        let (shutdown_sender, shutdown) = mpsc::channel(1);
+
+        let (inbound_sender, inbound) = mpsc::channel(INBOUND_QUEUE);
+
+        let mut schedule_queue = BinaryHeap::new();
+
+        schedule_queue.push(Schedule::new(Job::Autosave, time::Instant::now() + constants.autosave_interval));
+
+        schedule_queue.push(Schedule::new(Job::ReloadBans, time::Instant::now() + constants.bans_reload_interval));
+
+        Ok(Context {
             constants,
-            schedule_queue: BinaryHeap::new(),
-            listener,
-            waitings: Vec::new(),
+            schedule_queue,
+            listener: Some(listener),
+            shutdown,
+            shutdown_sender,
+            shutdown_at: None,
+            waitings: HashMap::new(),
+            waiting_serial: 0,
             connections: HashMap::new(),
-            map,
-        }
+            sessions: HashMap::new(),
+            inbound,
+            inbound_sender,
+            map: map.tiles,
+            grid,
+            terrain,
+            spawner,
+            spawn_region,
+            storage,
+            bans,
+            metrics: Arc::new(Metrics::new()),
+            admin,
+            admin_sender,
+        })
     }
 
-    pub async fn run(mut self) -> Result<(), Box<dyn Error>> {
+    ///
+    /// Handle jobs until a shutdown is drained or runs out of time,
+    /// and save everyone still playing.
+    /// 
+    pub async fn run(mut self) -> Result<(), Box<dyn std::error::Error>> {
         loop {
-            let job = selector::select_job(&mut self).await;
+            let job = match self.shutdown_at {
+                Some(deadline) => match time::timeout_at(deadline, selector::select_job(&mut self)).await {
+                    Ok(job) => job,
+                    Err(_) => {
+                        tracing::warn!("shutdown grace period is over");
+
+                        break;
+                    },
+                },
+                None => selector::select_job(&mut self).await,
+            };
+
+            job::handle(&mut self, job);
 
-            if let Err(e) = job::handle(&mut self, job) {
-                eprintln!("{e}");
+            self.metrics.set_sizes(self.connections.len(), self.waitings.len(), self.schedule_queue.len());
+
+            if self.is_drained() {
+                break;
             }
         }
+
+        // Saved before the connections are closed, and closed even when the save fails.
+        let saved = self.save_all();
+
+        self.close_all().await;
+
+        Ok(saved?)
+    }
+
+    ///
+    /// Numbers of the server, to be served from another task.
+    /// 
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    ///
+    /// Where commands of operators are sent, to be run as jobs.
+    /// 
+    pub fn admin(&self) -> mpsc::Sender<Request> {
+        self.admin_sender.clone()
+    }
+
+    ///
+    /// Where a request to shut down is sent, like on a signal.
+    /// 
+    pub fn shutdown(&self) -> mpsc::Sender<()> {
+        self.shutdown_sender.clone()
+    }
+
+    ///
+    /// Whether a shutdown has nothing due left to do.
+    /// 
+    /// Jobs scheduled later do not count. Autosave and the ban reload
+    /// always have a next run queued, so waiting for an empty queue
+    /// would always take the whole grace period, and what is left
+    /// undone is saved by `save_all` anyway.
+    /// 
+    fn is_drained(&self) -> bool {
+        if self.shutdown_at.is_none() {
+            return false;
+        }
+
+        let is_due = matches!(self.schedule_queue.peek(), Some(schedule) if schedule.deadline <= time::Instant::now());
+
+        !is_due
+    }
+
+    ///
+    /// Close every connection, waiting for what is queued
+    /// to be sent until the grace period is over.
+    /// 
+    async fn close_all(&mut self) {
+        let deadline = self.shutdown_at.unwrap_or_else(time::Instant::now);
+
+        let writers = self.connections.drain().filter_map(|(_, (stream, _, _))| stream.close()).collect::<Vec<_>>();
+
+        for writer in writers {
+            if time::timeout_at(deadline, writer).await.is_err() {
+                tracing::warn!("shutdown grace period is over, unsent packets are lost");
+
+                break;
+            }
+        }
+    }
+
+    ///
+    /// Save every connected character.
+    /// 
+    fn save_all(&mut self) -> Result<(), Error> {
+        let characters = self.connections.iter().map(|(id, (_, position, _))| (*id, Character { position: *position })).collect();
+
+        self.storage.save_all(characters).map_err(Error::Storage)
+    }
+
+    ///
+    /// Check that a connection holds every role of `required`,
+    /// for packets only staff may send.
+    /// 
+    pub fn authorize(&self, id: &[u8; 16], required: Roles) -> Result<(), Error> {
+        match self.connections.get(id) {
+            Some((_, _, roles)) if roles.contains(required) => Ok(()),
+            Some(_) => Err(GameError::Forbidden { id: *id, required }.into()),
+            None => Err(GameError::ConnectionNotFound(*id).into()),
+        }
+    }
+
+    ///
+    /// Ban an account, kicking its session out if it is playing.
+    /// 
+    pub fn ban(&mut self, id: [u8; 16], ban: Ban) -> Result<(), Error> {
+        self.bans.add(id, ban).map_err(Error::Storage)?;
+
+        self.kick_banned();
+
+        Ok(())
+    }
+
+    ///
+    /// Kick every session whose account is banned by now.
+    /// 
+    fn kick_banned(&mut self) {
+        let now = auth::now_millis();
+
+        let banned = self.connections.iter()
+            .filter(|(id, _)| self.bans.find(id, now).is_some())
+            .map(|(id, (stream, _, _))| (*id, stream.handle()))
+            .collect::<Vec<_>>();
+
+        for (id, handle) in banned {
+            self.metrics.dropped("banned");
+
+            self.send(&id, &packet::Outgoing::Kick { reason: packet::Reason::Banned }.serialize());
+
+            Context::schedule_drop(&mut self.schedule_queue, handle);
+        }
     }
 
-    fn schedule_drop(schedule_queue: &mut BinaryHeap<Schedule<Job>>, id: [u8; 16]) {
-        let job = Job::Drop(id);
+    ///
+    /// Drop the connection with `handle` once the current job is done.
+    /// 
+    /// It goes by the handle rather than the account, so a session
+    /// replacing the connection in the meantime is left alone.
+    /// 
+    fn schedule_drop(schedule_queue: &mut BinaryHeap<Schedule<Job>>, handle: u64) {
+        let job = Job::Drop(handle);
 
         let schedule = Schedule::now(job);
 
         schedule_queue.push(schedule);
     }
+
+    ///
+    /// Send a packet to a connection, dropping it if it cannot keep up.
+    /// 
+    /// A packet too large to encode is the fault of the server,
+    /// so it is only left out.
+    /// 
+    fn send(&mut self, id: &[u8; 16], buf: &[u8]) {
+        let (e, handle) = match self.connections.get_mut(id).map(|(stream, _, _)| (stream.send(buf), stream.handle())) {
+            Some((Err(e), handle)) => (e, handle),
+            _ => return,
+        };
+
+        match e {
+            net::Error::Overflow(_) => {},
+            net::Error::Io(ref error) if error.kind() == io::ErrorKind::BrokenPipe => {},
+            e => {
+                tracing::warn!(connection = %id.to_hex(), error = %e, "failed to send");
+
+                return;
+            },
+        }
+
+        let e = Error::from(e);
+
+        tracing::info!(connection = %id.to_hex(), error = %e, "failed to send");
+
+        self.metrics.dropped(e.label());
+
+        Context::schedule_drop(&mut self.schedule_queue, handle);
+    }
+
+    ///
+    /// Send a packet to every connection in sight of a position.
+    /// 
+    fn broadcast(&mut self, position: &Vector3, buf: &[u8]) {
+        for (id, _) in self.grid.nearby(position) {
+            self.send(&id, buf);
+        }
+    }
+
+    ///
+    /// Move a connection in the grid and tell everyone involved.
+    /// 
+    /// Those who keep seeing it get `buf`, those it leaves or
+    /// enters get `Leave` or `Enter`, and so does the moving one
+    /// about each of them.
+    /// 
+    fn relocate(&mut self, id: [u8; 16], from: &Vector3, to: Vector3, buf: &[u8]) {
+        let before = self.grid.nearby(from);
+
+        self.grid.relocate(id, from, to);
+
+        let after = self.grid.nearby(&to);
+
+        for (other, _) in before.iter() {
+            if after.iter().any(|(key, _)| key == other) {
+                self.send(other, buf);
+            } else {
+                self.send(other, &packet::Outgoing::Leave { id }.serialize());
+
+                self.send(&id, &packet::Outgoing::Leave { id: *other }.serialize());
+            }
+        }
+
+        for (other, position) in after.iter() {
+            if !before.iter().any(|(key, _)| key == other) {
+                self.send(other, &packet::Outgoing::Enter { id, x: to.x, y: to.y, z: to.z }.serialize());
+
+                self.send(&id, &packet::Outgoing::Enter { id: *other, x: position.x, y: position.y, z: position.z }.serialize());
+            }
+        }
+    }
+}
+
+///
+/// How many admin commands may wait for the loop at once.
+/// 
+const ADMIN_QUEUE: usize = 64;
+
+///
+/// How many events of connections may wait for the loop at once,
+/// before their readers stop reading.
+/// 
+const INBOUND_QUEUE: usize = 4096;
+
+///
+/// How many terrain kinds a single packet carries at most.
+/// 
+const TERRAIN_CHUNK: usize = 4096;
+
+///
+/// Lay the terrain of each level out as row-major rectangles,
+/// split into packets of whole rows.
+/// 
+fn serialize_terrain(map: &HashMap<Vector3, Tile>) -> Vec<Vec<u8>> {
+    let mut levels = map.keys().map(|position| position.y).collect::<Vec<_>>();
+
+    levels.sort();
+
+    levels.dedup();
+
+    let mut result = vec![];
+
+    for y in levels {
+        let positions = map.keys().filter(|position| position.y == y);
+
+        let (min_x, max_x, min_z, max_z) = positions.fold((i32::MAX, i32::MIN, i32::MAX, i32::MIN), |(min_x, max_x, min_z, max_z), position| {
+            (min_x.min(position.x), max_x.max(position.x), min_z.min(position.z), max_z.max(position.z))
+        });
+
+        let width = (max_x - min_x + 1) as usize;
+
+        let rows = (TERRAIN_CHUNK / width).max(1);
+
+        let mut z = min_z;
+
+        while z <= max_z {
+            let depth = rows.min((max_z - z + 1) as usize);
+
+            let mut kinds = Vec::with_capacity(width * depth);
+
+            for dz in 0..depth as i32 {
+                for x in min_x..=max_x {
+                    kinds.push(map.get(&Vector3::new(x, y, z + dz)).map(|tile| tile.terrain.serial()).unwrap_or(0));
+                }
+            }
+
+            result.push(packet::Outgoing::Terrain { x: min_x, y, z, width: width as u16, depth: depth as u16, kinds }.serialize());
+
+            z += depth as i32;
+        }
+    }
+
+    result
 }
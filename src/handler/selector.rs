@@ -1,87 +1,99 @@
-use std::{error::Error, collections::HashMap};
 use std::collections::BinaryHeap;
 
-use futures::future::select_all;
-use tokio::{time, net::TcpStream};
+use tokio::{net::{TcpListener, TcpStream}, time};
 
-use crate::common::math::Vector3;
 use crate::job::{Job, Schedule};
+use crate::net::Inbound;
 
 use super::Context;
 
 pub async fn select_job(context: &mut Context) -> Job {
-    if let Some(job) = get_late_schedule(&mut context.schedule_queue) {
-        return job;
+    if let Some(schedule) = get_late_schedule(&mut context.schedule_queue) {
+        return take(context, schedule);
     }
     
-    tokio::select! {
-        Ok((stream, _)) = context.listener.accept() => {
-            Job::Accept(stream)
-        },
-        Ok(_) = wait_first_schedule(&context.schedule_queue) => {
-            context.schedule_queue.pop().unwrap().job
-        },
-        Ok(index) = select_from_waitings(&mut context.waitings) => {
-            Job::Auth(index)
+    let is_shutting_down = context.shutdown_at.is_some();
+
+    loop {
+        let job = tokio::select! {
+            Some(_) = context.shutdown.recv(), if !is_shutting_down => {
+                Some(Job::Shutdown)
+            },
+            Some(request) = context.admin.recv() => {
+                Some(Job::Admin(request))
+            },
+            Some(stream) = accept(&context.listener) => {
+                Some(Job::Accept(stream))
+            },
+            Some(_) = wait_first_schedule(&context.schedule_queue) => {
+                let schedule = context.schedule_queue.pop().unwrap();
+
+                Some(take(context, schedule))
+            },
+            Some(inbound) = context.inbound.recv(), if !is_shutting_down => {
+                route(context, inbound)
+            },
+        };
+
+        if let Some(job) = job {
+            return job;
         }
-        Ok(id) = select_from_connections(&mut context.connections) => {
-            Job::Read(id.clone())
-        },
     }
 }
 
-fn get_late_schedule(schedule_queue: &mut BinaryHeap<Schedule<Job>>) -> Option<Job> {
-    if schedule_queue.is_empty() {
-        return None
+///
+/// The job for an event of a connection, by whether it is still
+/// waiting for its hello or playing already.
+/// 
+/// Events of connections dropped meanwhile are let go.
+/// 
+fn route(context: &Context, inbound: Inbound) -> Option<Job> {
+    let Inbound { handle, event } = inbound;
+
+    if context.waitings.contains_key(&handle) {
+        return Some(Job::Auth(handle, event));
     }
 
-    let first_schedule = schedule_queue.peek().unwrap();
-
-    if first_schedule.deadline > time::Instant::now() {
-        return None;
-    }
-
-    Some(schedule_queue.pop().unwrap().job)
-} 
+    context.sessions.get(&handle).map(|id| Job::Read(*id, event))
+}
 
-async fn wait_first_schedule(schedule_queue: &BinaryHeap<Schedule<Job>>) -> Result<(), Box<dyn Error>> {
-    if schedule_queue.is_empty() {
-        return Err("no schedule".into());
+///
+/// Next accepted socket, or never once the listener is closed.
+/// 
+async fn accept(listener: &Option<TcpListener>) -> Option<TcpStream> {
+    match listener {
+        Some(listener) => listener.accept().await.ok().map(|(stream, _)| stream),
+        None => std::future::pending().await,
     }
-    
-    let first_schedule = schedule_queue.peek().unwrap();
+}
 
-    time::sleep_until(first_schedule.deadline).await;
+///
+/// Take the job out of a schedule, recording how late it is.
+/// 
+fn take(context: &Context, schedule: Schedule<Job>) -> Job {
+    context.metrics.lateness(schedule.job.kind(), time::Instant::now().saturating_duration_since(schedule.deadline));
 
-    Ok(())
+    schedule.job
 }
 
-async fn select_from_waitings(waitings: &mut Vec<TcpStream>) -> Result<usize, Box<dyn Error>> {
-    if waitings.is_empty() {
-        return Err("no waiting".into());
+fn get_late_schedule(schedule_queue: &mut BinaryHeap<Schedule<Job>>) -> Option<Schedule<Job>> {
+    if schedule_queue.is_empty() {
+        return None
     }
 
-    match select_all(waitings.iter_mut().enumerate().map(|(index, stream)| Box::pin(async move {
-        stream.readable().await?;
+    let first_schedule = schedule_queue.peek().unwrap();
 
-        Ok::<usize, Box<dyn Error>>(index)
-    }))).await {
-        (Ok(index), _, _) => Ok(index),
-        (Err(e), _, _) => Err(e),
+    if first_schedule.deadline > time::Instant::now() {
+        return None;
     }
-}
 
-async fn select_from_connections(connections: &mut HashMap<[u8; 16], (TcpStream, Vector3)>) -> Result<&[u8; 16], Box<dyn Error>> {
-    if connections.is_empty() {
-        return Err("no connections".into())
-    }
+    schedule_queue.pop()
+} 
 
-    match select_all(connections.iter_mut().map(|(id, (stream, _))| Box::pin(async {
-        stream.readable().await?;
+async fn wait_first_schedule(schedule_queue: &BinaryHeap<Schedule<Job>>) -> Option<()> {
+    let first_schedule = schedule_queue.peek()?;
 
-        Ok::<&[u8; 16], Box<dyn Error>>(id)
-    }))).await {
-        (Ok(id), _, _) => Ok(id),
-        (Err(e), _, _) => Err(e),
-    }
+    time::sleep_until(first_schedule.deadline).await;
+
+    Some(())
 }
@@ -1,12 +1,41 @@
 use tokio::{time, net::TcpStream};
 
+use crate::admin::Request;
+use crate::auth::Token;
 use crate::common::math::Vector3;
+use crate::net::{Connection, Event};
 
 pub enum Job {
     Accept(TcpStream),
-    Auth(usize),
-    Read([u8; 16]),
-    Drop([u8; 16]),
-    Welcome(TcpStream, [u8; 16]),
+    Auth(u64, Event),
+    Read([u8; 16], Event),
+    Drop(u64),
+    Welcome(Connection, Token),
     Move { from: Vector3, tick: time::Duration },
+    Autosave,
+    Evict(u64),
+    ReloadBans,
+    Shutdown,
+    Admin(Request),
+}
+
+impl Job {
+    ///
+    /// Name of the kind of job, for logs.
+    /// 
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Job::Accept(_) => "accept",
+            Job::Auth(..) => "auth",
+            Job::Read(..) => "read",
+            Job::Drop(_) => "drop",
+            Job::Welcome(..) => "welcome",
+            Job::Move { .. } => "move",
+            Job::Autosave => "autosave",
+            Job::Evict(_) => "evict",
+            Job::ReloadBans => "reload_bans",
+            Job::Shutdown => "shutdown",
+            Job::Admin(_) => "admin",
+        }
+    }
 }
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod job;
 
 pub use job::Job;
@@ -25,7 +25,7 @@ impl<T> Eq for Schedule<T> {}
 
 impl<T> PartialOrd for Schedule<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.deadline.partial_cmp(&other.deadline).map(|ordering| ordering.reverse())
+        Some(self.cmp(other))
     }
 }
 
@@ -10,4 +10,14 @@ pub mod handler;
 
 pub mod auth;
 
-pub mod map;
\ No newline at end of file
+pub mod map;
+
+pub mod storage;
+
+pub mod logging;
+
+pub mod metrics;
+
+pub mod admin;
+
+pub mod client;
\ No newline at end of file
@@ -0,0 +1,45 @@
+use std::{error::Error, io::{self, IsTerminal}, str::FromStr};
+
+use tracing_subscriber::EnvFilter;
+
+///
+/// How log lines are written.
+/// 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    Human,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown log format, {s:?}").into()),
+        }
+    }
+}
+
+///
+/// Write logs to stderr from now on.
+/// 
+/// `level` is a filter like `info` or `warn,mmorpg::handler=debug`.
+/// 
+pub fn init(level: &str, format: Format) -> Result<(), Box<dyn Error>> {
+    let filter = match EnvFilter::try_new(level) {
+        Ok(filter) => filter,
+        Err(e) => return Err(format!("invalid log level {level:?}, {e}").into()),
+    };
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(io::stderr).with_ansi(io::stderr().is_terminal());
+
+    let result = match format {
+        Format::Human => builder.try_init(),
+        Format::Json => builder.json().flatten_event(true).with_current_span(false).with_span_list(true).try_init(),
+    };
+
+    result.map_err(|e| e as Box<dyn Error>)
+}
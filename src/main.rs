@@ -1,23 +1,48 @@
-use std::{error::Error, collections::HashMap};
+use std::error::Error;
 
-use mmorpg::{handler::Context, common::math::Vector3, constants::Constants, map::tile::Tile};
-use tokio::net::TcpListener;
+use mmorpg::{handler::Context, constants::Constants, map::loader, storage::FileStorage, auth::BanList, logging, metrics, admin};
+use tokio::{net::TcpListener, signal::unix::{self, SignalKind}};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let listener = TcpListener::bind("0.0.0.0:3000").await?;
+    let constants = Constants::init()?;
 
-    let mut map = HashMap::new();
-    
-    for x in 0..100 {
-        for z in 0..100 {
-            map.insert(Vector3::new(x, 0, z), Tile { object: None });
-        }
-    }
+    logging::init(&constants.log_level, constants.log_format)?;
 
-    let constants = Constants::init()?;
+    let mut terminate = unix::signal(SignalKind::terminate())?;
+
+    let mut interrupt = unix::signal(SignalKind::interrupt())?;
+
+    let listener = TcpListener::bind(constants.listen_address).await?;
+
+    let metrics_listener = TcpListener::bind(constants.metrics_address).await?;
+
+    let map = loader::load(&constants.map_path)?;
+
+    let storage = FileStorage::open(&constants.storage_path)?;
+
+    let bans = BanList::open(&constants.bans_path)?;
+
+    let admin_listener = admin::bind(&constants.admin_socket)?;
+
+    tracing::info!(address = %constants.listen_address, metrics = %constants.metrics_address, map = %constants.map_path, "listening");
+
+    let app = Context::new(constants, map, Box::new(storage), bans, listener)?;
+
+    tokio::spawn(metrics::serve(metrics_listener, app.metrics()));
+
+    tokio::spawn(admin::serve(admin_listener, app.admin()));
+
+    let shutdown = app.shutdown();
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = terminate.recv() => {},
+            _ = interrupt.recv() => {},
+        }
 
-    let app = Context::new(constants, map, listener);
+        let _ = shutdown.send(()).await;
+    });
 
     app.run().await
 }
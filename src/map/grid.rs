@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use crate::common::math::Vector3;
+
+///
+/// Spatial index of the humans in the world.
+/// 
+/// The map is split into square cells as wide as the view radius,
+/// so whoever is in sight of a position lives in the 3x3 cells around it.
+/// 
+pub struct Grid {
+    radius: i32,
+    cells: HashMap<(i32, i32), HashMap<[u8; 16], Vector3>>,
+}
+
+impl Grid {
+    pub fn new(radius: i32) -> Self {
+        Grid { radius: radius.max(1), cells: HashMap::new() }
+    }
+
+    fn cell_of(&self, position: &Vector3) -> (i32, i32) {
+        (position.x.div_euclid(self.radius), position.z.div_euclid(self.radius))
+    }
+
+    pub fn insert(&mut self, id: [u8; 16], position: Vector3) {
+        let cell = self.cell_of(&position);
+
+        self.cells.entry(cell).or_default().insert(id, position);
+    }
+
+    pub fn remove(&mut self, id: &[u8; 16], position: &Vector3) {
+        let cell = self.cell_of(position);
+
+        if let Some(members) = self.cells.get_mut(&cell) {
+            members.remove(id);
+
+            if members.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    pub fn relocate(&mut self, id: [u8; 16], from: &Vector3, to: Vector3) {
+        self.remove(&id, from);
+
+        self.insert(id, to);
+    }
+
+    pub fn in_sight(&self, a: &Vector3, b: &Vector3) -> bool {
+        (a.x - b.x).abs() <= self.radius && (a.y - b.y).abs() <= self.radius && (a.z - b.z).abs() <= self.radius
+    }
+
+    ///
+    /// Everyone in sight of a position, including whoever stands on it.
+    /// 
+    pub fn nearby(&self, position: &Vector3) -> Vec<([u8; 16], Vector3)> {
+        let (cx, cz) = self.cell_of(position);
+
+        let mut result = vec![];
+
+        for x in cx - 1..=cx + 1 {
+            for z in cz - 1..=cz + 1 {
+                if let Some(members) = self.cells.get(&(x, z)) {
+                    for (id, other) in members.iter() {
+                        if self.in_sight(position, other) {
+                            result.push((*id, *other));
+                        }
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
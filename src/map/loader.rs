@@ -0,0 +1,276 @@
+use std::{error::Error, collections::HashMap, fs, path::Path};
+
+use crate::common::math::Vector3;
+
+use super::{tile::Tile, terrain::{Terrain, WALK}};
+
+///
+/// A world as described by a map file.
+/// 
+/// The format is line based, and lines starting with `//` are comments:
+/// 
+/// ```text
+/// // a tiny field
+/// size 100 100
+/// spawn town 10 10
+/// spawn town 20 10 4 2
+/// grid
+/// ....#....
+/// ```
+/// 
+/// `size` gives the width along x and the depth along z,
+/// every `spawn` adds a point, or a rectangle of points when followed by
+/// its width and depth, to the named spawn region, and the rows after `grid`
+/// lay out the tiles, one row per z from 0 and one character per x.
+/// `.` is ground, `#` is a wall and `~` is water.
+/// 
+pub struct Map {
+    pub tiles: HashMap<Vector3, Tile>,
+    pub regions: Vec<Region>,
+}
+
+///
+/// Named group of spawn points.
+/// 
+pub struct Region {
+    pub name: String,
+    pub points: Vec<Vector3>,
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Map, Box<dyn Error>> {
+    let path = path.as_ref();
+
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => return Err(format!("failed to read map {path:?}, {e}").into()),
+    };
+
+    parse(&source)
+}
+
+pub fn parse(source: &str) -> Result<Map, Box<dyn Error>> {
+    let mut size = None;
+
+    let mut spawns = vec![];
+
+    let mut lines = source.lines().enumerate();
+
+    for (number, line) in lines.by_ref() {
+        let line = strip_comment(line);
+
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            None => continue,
+            Some("size") => size = Some((parse_number(words.next(), number)?, parse_number(words.next(), number)?)),
+            Some("spawn") => {
+                let name = match words.next() {
+                    Some(name) => name,
+                    None => return Err(format!("line {}: missing spawn region name", number + 1).into()),
+                };
+
+                let (x, z) = (parse_number(words.next(), number)?, parse_number(words.next(), number)?);
+
+                let (width, depth) = match words.next() {
+                    Some(width) => (parse_number(Some(width), number)?, parse_number(words.next(), number)?),
+                    None => (1, 1),
+                };
+
+                if width <= 0 || depth <= 0 {
+                    return Err(format!("line {}: invalid spawn size, {width}x{depth}", number + 1).into());
+                }
+
+                spawns.push((number, name, x, z, width, depth));
+            },
+            Some("grid") => break,
+            Some(word) => return Err(format!("line {}: unknown directive {word:?}", number + 1).into()),
+        }
+    }
+
+    let (width, depth) = match size {
+        Some(size) => size,
+        None => return Err("missing size before grid".into()),
+    };
+
+    if width <= 0 || depth <= 0 {
+        return Err(format!("invalid size, {width}x{depth}").into());
+    }
+
+    let mut regions: Vec<Region> = vec![];
+
+    for (number, name, x, z, w, d) in spawns {
+        let is_inside = |start: i32, length: i32, limit: i32| start >= 0 && start.checked_add(length).map(|end| end <= limit).unwrap_or(false);
+
+        if !is_inside(x, w, width) || !is_inside(z, d, depth) {
+            return Err(format!("line {}: spawn {name} of {w}x{d} at ({x}, {z}) is outside of size {width}x{depth}", number + 1).into());
+        }
+
+        let points = (z..z + d).flat_map(|z| (x..x + w).map(move |x| Vector3::new(x, 0, z)));
+
+        match regions.iter_mut().find(|region| region.name == name) {
+            Some(region) => region.points.extend(points),
+            None => regions.push(Region { name: name.to_owned(), points: points.collect() }),
+        }
+    }
+
+    let mut tiles = HashMap::new();
+
+    let mut z = 0;
+
+    for (number, line) in lines {
+        let line = strip_comment(line).trim_end();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if z >= depth {
+            return Err(format!("line {}: grid has more than {depth} rows", number + 1).into());
+        }
+
+        let mut x = 0;
+
+        for c in line.chars() {
+            let position = Vector3::new(x, 0, z);
+
+            if x >= width {
+                return Err(format!("line {}: tile at {position} is outside of width {width}", number + 1).into());
+            }
+
+            match Terrain::from_symbol(c) {
+                Some(terrain) => { tiles.insert(position, Tile::new(terrain)); },
+                None => return Err(format!("line {}: unknown tile {c:?} at {position}", number + 1).into()),
+            }
+
+            x += 1;
+        }
+
+        if x < width {
+            return Err(format!("line {}: row at z = {z} has {x} tiles, expected {width}", number + 1).into());
+        }
+
+        z += 1;
+    }
+
+    if z < depth {
+        return Err(format!("grid has {z} rows, expected {depth}").into());
+    }
+
+    if regions.is_empty() {
+        return Err("missing spawn".into());
+    }
+
+    for region in regions.iter() {
+        for spawn in region.points.iter() {
+            match tiles.get(spawn) {
+                Some(tile) if tile.terrain.passage() & WALK != 0 => {},
+                _ => return Err(format!("spawn {} at {spawn} is not a walkable tile", region.name).into()),
+            }
+        }
+    }
+
+    Ok(Map { tiles, regions })
+}
+
+fn strip_comment(line: &str) -> &str {
+    if line.trim_start().starts_with("//") {
+        return "";
+    }
+
+    line
+}
+
+fn parse_number(word: Option<&str>, number: usize) -> Result<i32, Box<dyn Error>> {
+    match word.map(str::parse) {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(e)) => Err(format!("line {}: {e}", number + 1).into()),
+        None => Err(format!("line {}: missing number", number + 1).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        match parse(source) {
+            Ok(_) => panic!("parsed, {source:?}"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn tiles_and_regions_are_laid_out() {
+        let map = parse("\
+// a walled corner
+size 3 2
+spawn town 0 0
+spawn town 1 1 2 1
+spawn dock 1 0
+grid
+..#
+~..
+").unwrap();
+
+        assert_eq!(map.tiles.len(), 6);
+
+        assert_eq!(map.tiles[&Vector3::new(2, 0, 0)].terrain, Terrain::Wall);
+
+        assert_eq!(map.tiles[&Vector3::new(0, 0, 1)].terrain, Terrain::Water);
+
+        let regions = map.regions.iter().map(|region| (region.name.as_str(), region.points.clone())).collect::<Vec<_>>();
+
+        assert_eq!(regions, vec![
+            ("town", vec![Vector3::new(0, 0, 0), Vector3::new(1, 0, 1), Vector3::new(2, 0, 1)]),
+            ("dock", vec![Vector3::new(1, 0, 0)]),
+        ]);
+    }
+
+    #[test]
+    fn rows_must_match_the_size() {
+        assert_eq!(error("size 2 1\nspawn a 0 0\ngrid\n.\n"), "line 4: row at z = 0 has 1 tiles, expected 2");
+
+        assert_eq!(error("size 2 1\nspawn a 0 0\ngrid\n...\n"), "line 4: tile at (2, 0, 0) is outside of width 2");
+
+        assert_eq!(error("size 1 1\nspawn a 0 0\ngrid\n.\n.\n"), "line 5: grid has more than 1 rows");
+
+        assert_eq!(error("size 1 2\nspawn a 0 0\ngrid\n.\n"), "grid has 1 rows, expected 2");
+    }
+
+    #[test]
+    fn spawns_must_be_walkable() {
+        assert_eq!(error("size 2 1\nspawn a 1 0\ngrid\n.#\n"), "spawn a at (1, 0, 0) is not a walkable tile");
+
+        assert_eq!(error("size 1 1\nspawn a 3 3\ngrid\n.\n"), "line 2: spawn a of 1x1 at (3, 3) is outside of size 1x1");
+
+        assert_eq!(error("size 1 1\ngrid\n.\n"), "missing spawn");
+    }
+
+    #[test]
+    fn spawn_rectangles_must_fit_the_size() {
+        assert_eq!(error("size 2 2\nspawn a 0 0 0 1\ngrid\n..\n..\n"), "line 2: invalid spawn size, 0x1");
+
+        assert_eq!(error("size 2 2\nspawn a 0 0 1 -1\ngrid\n..\n..\n"), "line 2: invalid spawn size, 1x-1");
+
+        assert_eq!(error("size 2 2\nspawn a 1 0 2 1\ngrid\n..\n..\n"), "line 2: spawn a of 2x1 at (1, 0) is outside of size 2x2");
+
+        assert_eq!(error("size 2 2\nspawn a 0 -1 1 2\ngrid\n..\n..\n"), "line 2: spawn a of 1x2 at (0, -1) is outside of size 2x2");
+
+        assert_eq!(error("size 2 2\nspawn a 2147483647 0 2147483647 1\ngrid\n..\n..\n"), "line 2: spawn a of 2147483647x1 at (2147483647, 0) is outside of size 2x2");
+    }
+
+    #[test]
+    fn directives_are_checked() {
+        assert_eq!(error("grid\n.\n"), "missing size before grid");
+
+        assert_eq!(error("size 0 1\ngrid\n"), "invalid size, 0x1");
+
+        assert_eq!(error("size 1\n"), "line 1: missing number");
+
+        assert_eq!(error("spawn\n"), "line 1: missing spawn region name");
+
+        assert_eq!(error("wall 1 1\n"), "line 1: unknown directive \"wall\"");
+
+        assert_eq!(error("size 1 1\nspawn a 0 0\ngrid\n?\n"), "line 4: unknown tile '?' at (0, 0, 0)");
+    }
+}
@@ -1,3 +1,11 @@
 pub mod tile;
 
-pub mod object;
\ No newline at end of file
+pub mod terrain;
+
+pub mod object;
+
+pub mod grid;
+
+pub mod loader;
+
+pub mod spawn;
@@ -1,5 +1,7 @@
 use tokio::time;
 
+use super::terrain::WALK;
+
 pub enum Object {
     Human {
         id: [u8; 16],
@@ -11,6 +13,15 @@ impl Object {
     pub fn new_human(id: [u8; 16]) -> Self {
         Object::Human { id, state: HumanState::Idle { updated_at: None } }
     }
+
+    ///
+    /// Flags of the ways this object gets across tiles.
+    /// 
+    pub fn passage(&self) -> u8 {
+        match self {
+            Object::Human { .. } => WALK,
+        }
+    }
 }
 
 pub enum HumanState {
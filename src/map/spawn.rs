@@ -0,0 +1,97 @@
+use std::{collections::HashMap, error::Error, str::FromStr, time::SystemTime};
+
+use crate::common::math::Vector3;
+
+use super::{grid::Grid, loader::Region, object::Object, tile::Tile};
+
+///
+/// How a spawn point is picked among the free ones of a region.
+/// 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Policy {
+    Random,
+    RoundRobin,
+    LeastCrowded,
+}
+
+impl FromStr for Policy {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Policy::Random),
+            "round-robin" => Ok(Policy::RoundRobin),
+            "least-crowded" => Ok(Policy::LeastCrowded),
+            _ => Err(format!("unknown spawn policy, {s:?}").into()),
+        }
+    }
+}
+
+pub struct Spawner {
+    policy: Policy,
+    regions: Vec<Region>,
+    cursors: HashMap<String, usize>,
+    seed: u64,
+}
+
+impl Spawner {
+    pub fn new(policy: Policy, regions: Vec<Region>) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or(0)
+            | 1;
+
+        Spawner { policy, regions, cursors: HashMap::new(), seed }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.regions.iter().any(|region| region.name == name)
+    }
+
+    ///
+    /// Name of the region used when none is asked for, the first one in the map.
+    /// 
+    pub fn default_region(&self) -> &str {
+        &self.regions[0].name
+    }
+
+    ///
+    /// Pick a spawn point of the region the object could stand on.
+    /// 
+    /// `None` means every point of the region is taken.
+    /// 
+    pub fn pick(&mut self, name: &str, object: &Object, map: &HashMap<Vector3, Tile>, grid: &Grid) -> Option<Vector3> {
+        let points = &self.regions.iter().find(|region| region.name == name)?.points;
+
+        if points.is_empty() {
+            return None;
+        }
+
+        let is_free = |point: &Vector3| map.get(point).map(|tile| tile.is_passable_by(object)).unwrap_or(false);
+
+        match self.policy {
+            Policy::Random => {
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 7;
+                self.seed ^= self.seed << 17;
+
+                let start = (self.seed % points.len() as u64) as usize;
+
+                (0..points.len()).map(|i| points[(start + i) % points.len()]).find(is_free)
+            },
+            Policy::RoundRobin => {
+                let cursor = self.cursors.entry(name.to_owned()).or_default();
+
+                let index = (0..points.len()).map(|i| (*cursor + i) % points.len()).find(|index| is_free(&points[*index]))?;
+
+                *cursor = index + 1;
+
+                Some(points[index])
+            },
+            Policy::LeastCrowded => {
+                points.iter().filter(|point| is_free(point)).min_by_key(|point| grid.nearby(point).len()).copied()
+            },
+        }
+    }
+}
@@ -0,0 +1,49 @@
+///
+/// Ways of getting across a tile, as bit flags.
+/// 
+pub const WALK: u8 = 0b01;
+
+pub const SWIM: u8 = 0b10;
+
+///
+/// What a tile is made of.
+/// 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Terrain {
+    Ground,
+    Wall,
+    Water,
+}
+
+impl Terrain {
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '.' => Some(Terrain::Ground),
+            '#' => Some(Terrain::Wall),
+            '~' => Some(Terrain::Water),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Number identifying the terrain on the wire, 0 is kept for no tile.
+    /// 
+    pub fn serial(&self) -> u8 {
+        match self {
+            Terrain::Ground => 1,
+            Terrain::Wall => 2,
+            Terrain::Water => 3,
+        }
+    }
+
+    ///
+    /// Flags of the ways this terrain can be crossed.
+    /// 
+    pub fn passage(&self) -> u8 {
+        match self {
+            Terrain::Ground => WALK,
+            Terrain::Wall => 0,
+            Terrain::Water => SWIM,
+        }
+    }
+}
@@ -1,5 +1,19 @@
-use super::object::Object;
+use super::{object::Object, terrain::Terrain};
 
 pub struct Tile {
+    pub terrain: Terrain,
     pub object: Option<Object>
-}
\ No newline at end of file
+}
+
+impl Tile {
+    pub fn new(terrain: Terrain) -> Self {
+        Tile { terrain, object: None }
+    }
+
+    ///
+    /// Whether the object could step onto this tile right now.
+    /// 
+    pub fn is_passable_by(&self, object: &Object) -> bool {
+        self.object.is_none() && self.terrain.passage() & object.passage() != 0
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::BTreeMap, fmt::Write, sync::{Mutex, atomic::{AtomicUsize, Ordering}}, time::Duration};
+
+mod server;
+
+pub use server::serve;
+
+///
+/// Upper bounds of the lateness buckets, in seconds.
+/// 
+const LATENESS_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+///
+/// Which way a packet went.
+/// 
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+impl Direction {
+    fn label(&self) -> &'static str {
+        match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Traffic {
+    packets: u64,
+    bytes: u64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENESS_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+///
+/// Numbers of the running server, shared with the task serving them.
+/// 
+/// The game loop only ever writes, and every lock is held for
+/// a single update, so scraping never holds the loop up for long.
+/// 
+#[derive(Default)]
+pub struct Metrics {
+    connections: AtomicUsize,
+    waitings: AtomicUsize,
+    queue_depth: AtomicUsize,
+    traffic: Mutex<BTreeMap<(Direction, &'static str), Traffic>>,
+    jobs: Mutex<BTreeMap<&'static str, u64>>,
+    lateness: Mutex<BTreeMap<&'static str, Histogram>>,
+    drops: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn set_sizes(&self, connections: usize, waitings: usize, queue_depth: usize) {
+        self.connections.store(connections, Ordering::Relaxed);
+
+        self.waitings.store(waitings, Ordering::Relaxed);
+
+        self.queue_depth.store(queue_depth, Ordering::Relaxed);
+    }
+
+    ///
+    /// Count a frame of `size` bytes, its length prefix included.
+    /// 
+    pub fn packet(&self, direction: Direction, kind: &'static str, size: usize) {
+        let mut traffic = self.traffic.lock().unwrap();
+
+        let traffic = traffic.entry((direction, kind)).or_default();
+
+        traffic.packets += 1;
+
+        traffic.bytes += size as u64;
+    }
+
+    pub fn job(&self, kind: &'static str) {
+        *self.jobs.lock().unwrap().entry(kind).or_default() += 1;
+    }
+
+    ///
+    /// Record how long after its deadline a scheduled job started.
+    /// 
+    pub fn lateness(&self, kind: &'static str, lateness: Duration) {
+        let seconds = lateness.as_secs_f64();
+
+        let mut lateness = self.lateness.lock().unwrap();
+
+        let histogram = lateness.entry(kind).or_default();
+
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENESS_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+
+        histogram.count += 1;
+
+        histogram.sum += seconds;
+    }
+
+    pub fn dropped(&self, reason: &'static str) {
+        *self.drops.lock().unwrap().entry(reason).or_default() += 1;
+    }
+
+    ///
+    /// Write everything out in the Prometheus text format.
+    /// 
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let gauges = [
+            ("mmorpg_connections", "Players in the game.", &self.connections),
+            ("mmorpg_waitings", "Sockets waiting for their hello.", &self.waitings),
+            ("mmorpg_schedule_queue_depth", "Jobs scheduled and not run yet.", &self.queue_depth),
+        ];
+
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {}", value.load(Ordering::Relaxed));
+        }
+
+        let traffic = self.traffic.lock().unwrap();
+
+        let _ = writeln!(out, "# HELP mmorpg_packets_total Frames by direction and packet type.\n# TYPE mmorpg_packets_total counter");
+
+        for ((direction, kind), traffic) in traffic.iter() {
+            let _ = writeln!(out, "mmorpg_packets_total{{direction=\"{}\",type=\"{kind}\"}} {}", direction.label(), traffic.packets);
+        }
+
+        let _ = writeln!(out, "# HELP mmorpg_bytes_total Bytes of frames by direction and packet type.\n# TYPE mmorpg_bytes_total counter");
+
+        for ((direction, kind), traffic) in traffic.iter() {
+            let _ = writeln!(out, "mmorpg_bytes_total{{direction=\"{}\",type=\"{kind}\"}} {}", direction.label(), traffic.bytes);
+        }
+
+        drop(traffic);
+
+        let _ = writeln!(out, "# HELP mmorpg_jobs_total Jobs handled by kind.\n# TYPE mmorpg_jobs_total counter");
+
+        for (kind, count) in self.jobs.lock().unwrap().iter() {
+            let _ = writeln!(out, "mmorpg_jobs_total{{kind=\"{kind}\"}} {count}");
+        }
+
+        let _ = writeln!(out, "# HELP mmorpg_job_lateness_seconds How long after its deadline a scheduled job started.\n# TYPE mmorpg_job_lateness_seconds histogram");
+
+        for (kind, histogram) in self.lateness.lock().unwrap().iter() {
+            for (count, bound) in histogram.buckets.iter().zip(LATENESS_BUCKETS) {
+                let _ = writeln!(out, "mmorpg_job_lateness_seconds_bucket{{kind=\"{kind}\",le=\"{bound}\"}} {count}");
+            }
+
+            let _ = writeln!(out, "mmorpg_job_lateness_seconds_bucket{{kind=\"{kind}\",le=\"+Inf\"}} {}", histogram.count);
+
+            let _ = writeln!(out, "mmorpg_job_lateness_seconds_sum{{kind=\"{kind}\"}} {}", histogram.sum);
+
+            let _ = writeln!(out, "mmorpg_job_lateness_seconds_count{{kind=\"{kind}\"}} {}", histogram.count);
+        }
+
+        let _ = writeln!(out, "# HELP mmorpg_drops_total Clients dropped or turned away by reason.\n# TYPE mmorpg_drops_total counter");
+
+        for (reason, count) in self.drops.lock().unwrap().iter() {
+            let _ = writeln!(out, "mmorpg_drops_total{{reason=\"{reason}\"}} {count}");
+        }
+
+        out
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+
+use super::Metrics;
+
+///
+/// Largest request head read before answering.
+/// 
+const MAX_REQUEST_SIZE: usize = 4096;
+
+///
+/// Answer `GET /metrics` on every socket the listener accepts.
+/// 
+/// Meant to be spawned on its own task, so a slow scraper
+/// never holds the game loop up.
+/// 
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to accept a metrics scrape");
+
+                continue;
+            },
+        };
+
+        let metrics = metrics.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &metrics).await {
+                tracing::debug!(error = %e, "failed to answer a metrics scrape");
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let mut request = Vec::new();
+
+    let mut chunk = [0u8; 1024];
+
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let n = stream.read(&mut chunk).await?;
+
+        if n == 0 {
+            break;
+        }
+
+        request.extend_from_slice(&chunk[..n]);
+    }
+
+    let is_metrics = request.starts_with(b"GET /metrics ") || request.starts_with(b"GET / ");
+
+    let (status, body) = match is_metrics {
+        true => ("200 OK", metrics.render()),
+        false => ("404 Not Found", String::from("not found\n")),
+    };
+
+    let response = format!("HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len());
+
+    stream.write_all(response.as_bytes()).await?;
+
+    stream.shutdown().await
+}
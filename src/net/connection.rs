@@ -0,0 +1,196 @@
+use std::{io, sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::Duration};
+
+use tokio::{io::AsyncWriteExt, net::{TcpStream, tcp::{OwnedReadHalf, OwnedWriteHalf}}, sync::mpsc, task::JoinHandle, time};
+
+use crate::metrics::{Direction, Metrics};
+
+use super::{io::{self as frame, Decoder}, error::Error, packet};
+
+///
+/// How long the writer of a dropped connection may take
+/// to send what is still queued, before the socket is cut.
+///
+const LINGER: Duration = Duration::from_secs(5);
+
+///
+/// What the reader or the writer of a connection has to tell the game loop.
+///
+#[derive(Debug)]
+pub enum Event {
+    Frame(Vec<u8>),
+    Closed,
+    Failed(Error),
+}
+
+impl Event {
+    ///
+    /// The frame, or why none will come anymore.
+    ///
+    pub fn into_frame(self) -> Result<Vec<u8>, Error> {
+        match self {
+            Event::Frame(buf) => Ok(buf),
+            Event::Closed => Err(Error::Io(io::Error::from(io::ErrorKind::UnexpectedEof))),
+            Event::Failed(e) => Err(e),
+        }
+    }
+}
+
+///
+/// An event of the connection with `handle`.
+///
+#[derive(Debug)]
+pub struct Inbound {
+    pub handle: u64,
+    pub event: Event,
+}
+
+///
+/// A socket read and written by tasks of its own.
+///
+/// The reader decodes frames and passes them into the game loop
+/// through the inbound channel, the writer drains the outbound queue.
+/// Dropping it stops the reader, while the writer still sends what
+/// is queued before closing the socket, for a while at most.
+///
+pub struct Connection {
+    handle: u64,
+    outbound: mpsc::UnboundedSender<Vec<u8>>,
+    queued: Arc<AtomicUsize>,
+    high_water: usize,
+    reader: JoinHandle<()>,
+    writer: Option<JoinHandle<()>>,
+    metrics: Arc<Metrics>,
+}
+
+impl Connection {
+    ///
+    /// `high_water` is how many bytes may wait in the outbound queue
+    /// before the peer is considered too slow to keep, and
+    /// `max_packet_size` how large a frame it may send.
+    ///
+    pub fn spawn(stream: TcpStream, handle: u64, high_water: usize, max_packet_size: usize, metrics: Arc<Metrics>, inbound: mpsc::Sender<Inbound>) -> Self {
+        let (read_half, write_half) = stream.into_split();
+
+        let (outbound, queue) = mpsc::unbounded_channel();
+
+        let queued = Arc::new(AtomicUsize::new(0));
+
+        let reader = tokio::spawn(read(read_half, handle, Decoder::new(max_packet_size), metrics.clone(), inbound.clone()));
+
+        let writer = Some(tokio::spawn(write(write_half, handle, queue, queued.clone(), inbound)));
+
+        Connection { handle, outbound, queued, high_water, reader, writer, metrics }
+    }
+
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+
+    ///
+    /// Queue a frame for the writer.
+    ///
+    /// Fails only when the queue grows past the high-water mark
+    /// or the writer is gone.
+    ///
+    pub fn send(&mut self, body: &[u8]) -> Result<(), Error> {
+        let mut buf = Vec::with_capacity(2 + body.len());
+
+        frame::encode_into(&mut buf, body)?;
+
+        let queued = self.queued.load(Ordering::Relaxed) + buf.len();
+
+        if queued > self.high_water {
+            return Err(Error::Overflow(queued))
+        }
+
+        self.queued.fetch_add(buf.len(), Ordering::Relaxed);
+
+        self.metrics.packet(Direction::Out, packet::Outgoing::name(serial_of(body)), buf.len());
+
+        if self.outbound.send(buf).is_err() {
+            return Err(Error::Io(io::Error::from(io::ErrorKind::BrokenPipe)))
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Drop the connection, handing back the writer
+    /// to wait for what is still queued to go out.
+    ///
+    pub fn close(mut self) -> Option<JoinHandle<()>> {
+        self.writer.take()
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+
+        if let Some(mut writer) = self.writer.take() {
+            tokio::spawn(async move {
+                if time::timeout(LINGER, &mut writer).await.is_err() {
+                    writer.abort();
+                }
+            });
+        }
+    }
+}
+
+async fn read(mut stream: OwnedReadHalf, handle: u64, mut decoder: Decoder, metrics: Arc<Metrics>, inbound: mpsc::Sender<Inbound>) {
+    let event = loop {
+        match decoder.read_from(&mut stream).await {
+            Ok(0) => break Event::Closed,
+            Ok(_) => {},
+            Err(e) => break Event::Failed(Error::Io(e)),
+        }
+
+        loop {
+            match decoder.next_frame() {
+                Ok(Some(frame)) => {
+                    metrics.packet(Direction::In, packet::Incoming::name(serial_of(&frame)), 2 + frame.len());
+
+                    if inbound.send(Inbound { handle, event: Event::Frame(frame) }).await.is_err() {
+                        return;
+                    }
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = inbound.send(Inbound { handle, event: Event::Failed(Error::Decode(e)) }).await;
+
+                    return;
+                },
+            }
+        }
+    };
+
+    let _ = inbound.send(Inbound { handle, event }).await;
+}
+
+async fn write(mut stream: OwnedWriteHalf, handle: u64, mut queue: mpsc::UnboundedReceiver<Vec<u8>>, queued: Arc<AtomicUsize>, inbound: mpsc::Sender<Inbound>) {
+    while let Some(mut buf) = queue.recv().await {
+        // Whatever else is queued goes out in the same write.
+        while let Ok(more) = queue.try_recv() {
+            buf.extend_from_slice(&more);
+        }
+
+        if let Err(e) = stream.write_all(&buf).await {
+            queued.store(0, Ordering::Relaxed);
+
+            let _ = inbound.send(Inbound { handle, event: Event::Failed(Error::Io(e)) }).await;
+
+            return;
+        }
+
+        queued.fetch_sub(buf.len(), Ordering::Relaxed);
+    }
+
+    let _ = stream.shutdown().await;
+}
+
+fn serial_of(body: &[u8]) -> u16 {
+    match body {
+        [low, high, ..] => u16::from_le_bytes([*low, *high]),
+        _ => 0,
+    }
+}
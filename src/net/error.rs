@@ -0,0 +1,62 @@
+use std::{fmt, io};
+
+///
+/// A peer sent bytes that do not follow the protocol.
+/// 
+#[derive(Debug)]
+pub enum DecodeError {
+    FrameSize(usize),
+    TooShort { serial: Option<u16>, length: usize },
+    UnknownPacket(u16),
+    InvalidArgument { serial: u16, value: u8 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::FrameSize(size) => write!(f, "invalid frame size, {size}"),
+            DecodeError::TooShort { serial: Some(serial), length } => write!(f, "packet {serial} too short, {length} bytes"),
+            DecodeError::TooShort { serial: None, length } => write!(f, "packet too short, {length} bytes"),
+            DecodeError::UnknownPacket(serial) => write!(f, "unexpected packet arrived, {serial}"),
+            DecodeError::InvalidArgument { serial, value } => write!(f, "unexpected argument of packet {serial}, {value}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+///
+/// Failure on a connection.
+/// 
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Decode(DecodeError),
+    TooLarge(usize),
+    Overflow(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Decode(e) => write!(f, "{e}"),
+            Error::TooLarge(size) => write!(f, "packet too large to send, {size} bytes"),
+            Error::Overflow(queued) => write!(f, "outbound queue overflow, {queued} bytes"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
@@ -1,42 +1,45 @@
-use std::error::Error;
-
 use crate::common::Bytes;
 
+use super::error::DecodeError;
+
 #[derive(Debug)]
 pub enum Incoming {
     Ping { timestamp: i64 },
     Hello { token: String },
-    Move { direction: u8 }
+    Move { direction: u8 },
+    Announce { message: String },
 }
 
 impl Incoming {
-    pub fn deserialize(buf: &[u8]) -> Result<Self, Box<dyn Error>> {
+    pub fn deserialize(buf: &[u8]) -> Result<Self, DecodeError> {
         if buf.len() < 2 {
-            return Err(format!("buffer too short to deserialize, {buf:?}").into())
+            return Err(DecodeError::TooShort { serial: None, length: buf.len() })
         }
 
         let serial = u16::from_le_bytes([buf[0], buf[1]]);
 
         let body = &buf[2..];
 
+        let too_short = DecodeError::TooShort { serial: Some(serial), length: buf.len() };
+
         match serial {
             1 => {
                 if body.len() < 8 {
-                    return Err(format!("buffer too short to deserialize, {buf:?}").into())
+                    return Err(too_short)
                 }
 
-                Ok(Self::Ping { timestamp: i64::from_le_bytes(body.clone_into_array()) })
+                Ok(Self::Ping { timestamp: i64::from_le_bytes(body[..8].clone_into_array()) })
             },
             2 => {
-                if body.len() < 1 {
-                    return Err(format!("buffer too short to deserialize, {buf:?}").into())
+                if body.is_empty() {
+                    return Err(too_short)
                 }
 
                 Ok(Incoming::Hello { token: String::from_utf8_lossy(body).into_owned() })
             },
             3 => {
-                if body.len() < 1 {
-                    return Err(format!("buffer too short to deserialize, {buf:?}").into())
+                if body.is_empty() {
+                    return Err(too_short)
                 }
 
                 match body[0] {
@@ -45,10 +48,54 @@ impl Incoming {
                     2 => Ok(Self::Move { direction: 2 }),
                     3 => Ok(Self::Move { direction: 3 }),
                     4 => Ok(Self::Move { direction: 4 }),
-                    _ => Err("unexpected arguments".into())
+                    value => Err(DecodeError::InvalidArgument { serial, value })
                 }
             },
-            n => Err(format!("unexpected packet arrived, {n:?}").into())
+            4 => {
+                if body.is_empty() {
+                    return Err(too_short)
+                }
+
+                Ok(Incoming::Announce { message: String::from_utf8_lossy(body).into_owned() })
+            },
+            n => Err(DecodeError::UnknownPacket(n))
+        }
+    }
+
+    ///
+    /// The body a client sends, the counterpart of `deserialize`.
+    /// 
+    pub fn serialize(self) -> Vec<u8> {
+        match self {
+            Incoming::Ping { timestamp } => [
+                &[1u8, 0] as &[u8],
+                &timestamp.to_le_bytes(),
+            ].concat(),
+            Incoming::Hello { token } => [
+                &[2u8, 0] as &[u8],
+                token.as_bytes(),
+            ].concat(),
+            Incoming::Move { direction } => [
+                &[3u8, 0] as &[u8],
+                &[direction],
+            ].concat(),
+            Incoming::Announce { message } => [
+                &[4u8, 0] as &[u8],
+                message.as_bytes(),
+            ].concat(),
+        }
+    }
+
+    ///
+    /// Name of the packet a serial stands for, for metrics.
+    /// 
+    pub fn name(serial: u16) -> &'static str {
+        match serial {
+            1 => "ping",
+            2 => "hello",
+            3 => "move",
+            4 => "announce",
+            _ => "unknown",
         }
     }
 }
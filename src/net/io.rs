@@ -1,82 +1,188 @@
 use std::io;
 
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
-pub trait Reader {
-    fn try_read_one(&self, buf: &mut Vec<u8>) -> io::Result<()>;
+use super::error::{Error, DecodeError};
 
-    fn try_read_to_end(&self, buf: &mut [u8]) -> io::Result<()>;
+const CHUNK_SIZE: usize = 4096;
+
+///
+/// Stateful frame decoder of a connection.
+///
+/// Bytes are kept between reads, so a frame split over
+/// several reads is never lost.
+///
+pub struct Decoder {
+    buf: Vec<u8>,
+    max_size: usize,
 }
 
-pub trait Writer {
-    fn try_write_one(&self, buf: &mut Vec<u8>) -> io::Result<()>;
+impl Decoder {
+    ///
+    /// `max_size` is the largest frame body accepted.
+    ///
+    pub fn new(max_size: usize) -> Self {
+        Decoder { buf: Vec::new(), max_size }
+    }
 
-    fn try_write_to_end(&self, buf: &mut [u8]) -> io::Result<()>;
-}
+    ///
+    /// Wait for bytes on the stream and buffer them,
+    /// telling how many came, `0` being the end of the stream.
+    ///
+    pub async fn read_from(&mut self, stream: &mut (impl AsyncRead + Unpin)) -> io::Result<usize> {
+        let mut chunk = [0u8; CHUNK_SIZE];
 
-impl Reader for TcpStream {
-    fn try_read_one(&self, buf: &mut Vec<u8>) -> io::Result<()> {
-        if buf.len() < 2 {
-            buf.resize(2, 0);
-        }
+        let n = stream.read(&mut chunk).await?;
 
-        self.try_read_to_end(&mut buf[..2])?;
+        self.buf.extend_from_slice(&chunk[..n]);
 
-        let size = usize::from(u16::from_le_bytes([buf[0], buf[1]]));
+        Ok(n)
+    }
 
-        if size == 0 {
-            return Err(io::Error::new(io::ErrorKind::Other, format!("invalid size, {size}")))
+    ///
+    /// Take the next complete frame out of the buffer, if any.
+    ///
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, DecodeError> {
+        if self.buf.len() < 2 {
+            return Ok(None);
         }
 
-        if size > 8096 {
-            return Err(io::Error::new(io::ErrorKind::Other, format!("packet too large, {size}")))
+        let size = usize::from(u16::from_le_bytes([self.buf[0], self.buf[1]]));
+
+        if size == 0 || size > self.max_size {
+            return Err(DecodeError::FrameSize(size))
         }
 
-        buf.resize(size, 0);
+        if self.buf.len() < 2 + size {
+            return Ok(None);
+        }
 
-        self.try_read_to_end(buf)?;
+        let frame = self.buf[2..2 + size].to_vec();
 
-        Ok(())
-    }
-    
-    fn try_read_to_end(&self, buf: &mut [u8]) -> io::Result<()> {
-        let mut pos = 0;
-
-        while pos < buf.len() {
-            match self.try_read(&mut buf[pos..]) {
-                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
-                Ok(n) => { pos += n; },
-                Err(e) => return Err(e),
-            }
-        }
+        self.buf.drain(..2 + size);
 
-        Ok(())
+        Ok(Some(frame))
     }
 }
 
-impl Writer for TcpStream {
-    fn try_write_one(&self, buf: &mut Vec<u8>) -> io::Result<()> {
-        let size: u16 = match buf.len().try_into() {
-            Ok(size) => size,
-            Err(_) => return Err(io::Error::new(io::ErrorKind::Other, "buffer too large"))
-        };
+///
+/// Append a frame carrying `body` to `buf`.
+///
+pub fn encode_into(buf: &mut Vec<u8>, body: &[u8]) -> Result<(), Error> {
+    let size: u16 = match body.len().try_into() {
+        Ok(size) => size,
+        Err(_) => return Err(Error::TooLarge(body.len()))
+    };
+
+    buf.extend_from_slice(&size.to_le_bytes());
+
+    buf.extend_from_slice(body);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
 
-        let mut buf = [&u16::to_le_bytes(size) as &[u8], buf].concat();
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
 
-        self.try_write_to_end(&mut buf)
+        encode_into(&mut buf, body).unwrap();
+
+        buf
     }
 
-    fn try_write_to_end(&self, buf: &mut [u8]) -> io::Result<()> {
-        let mut pos = 0;
+    #[tokio::test]
+    async fn frames_split_over_reads_are_joined() {
+        let (mut client, mut server) = tokio::io::duplex(64);
 
-        while pos < buf.len() {
-            match self.try_write(&mut buf[pos..]) {
-                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
-                Ok(n) => { pos += n; },
-                Err(e) => return Err(e),
-            }
-        }
+        let mut decoder = Decoder::new(16);
+
+        let buf = frame(b"hello");
+
+        client.write_all(&buf[..1]).await.unwrap();
+
+        decoder.read_from(&mut server).await.unwrap();
+
+        assert_eq!(decoder.next_frame().unwrap(), None);
+
+        client.write_all(&buf[1..4]).await.unwrap();
 
-        Ok(())
+        decoder.read_from(&mut server).await.unwrap();
+
+        assert_eq!(decoder.next_frame().unwrap(), None);
+
+        client.write_all(&buf[4..]).await.unwrap();
+
+        decoder.read_from(&mut server).await.unwrap();
+
+        assert_eq!(decoder.next_frame().unwrap(), Some(b"hello".to_vec()));
+
+        assert_eq!(decoder.next_frame().unwrap(), None);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn frames_of_one_read_come_out_in_order() {
+        let buf = [frame(b"a"), frame(b"bc"), frame(b"def")[..3].to_vec()].concat();
+
+        let mut decoder = Decoder::new(16);
+
+        decoder.read_from(&mut buf.as_slice()).await.unwrap();
+
+        assert_eq!(decoder.next_frame().unwrap(), Some(b"a".to_vec()));
+
+        assert_eq!(decoder.next_frame().unwrap(), Some(b"bc".to_vec()));
+
+        assert_eq!(decoder.next_frame().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn buffered_frames_outlive_the_end_of_the_stream() {
+        let buf = [frame(b"last"), frame(b"words")].concat();
+
+        let mut stream = buf.as_slice();
+
+        let mut decoder = Decoder::new(16);
+
+        assert_eq!(decoder.read_from(&mut stream).await.unwrap(), buf.len());
+
+        assert_eq!(decoder.read_from(&mut stream).await.unwrap(), 0);
+
+        assert_eq!(decoder.next_frame().unwrap(), Some(b"last".to_vec()));
+
+        assert_eq!(decoder.next_frame().unwrap(), Some(b"words".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn frame_sizes_are_bounded() {
+        let mut decoder = Decoder::new(4);
+
+        decoder.read_from(&mut frame(b"four").as_slice()).await.unwrap();
+
+        assert_eq!(decoder.next_frame().unwrap(), Some(b"four".to_vec()));
+
+        decoder.read_from(&mut frame(b"fives").as_slice()).await.unwrap();
+
+        assert!(matches!(decoder.next_frame(), Err(DecodeError::FrameSize(5))));
+
+        let mut decoder = Decoder::new(4);
+
+        decoder.read_from(&mut [0u8, 0].as_slice()).await.unwrap();
+
+        assert!(matches!(decoder.next_frame(), Err(DecodeError::FrameSize(0))));
+    }
+
+    #[test]
+    fn bodies_beyond_a_length_prefix_are_not_encoded() {
+        let mut buf = Vec::new();
+
+        encode_into(&mut buf, &[0; u16::MAX as usize]).unwrap();
+
+        assert_eq!(buf.len(), 2 + u16::MAX as usize);
+
+        assert!(matches!(encode_into(&mut buf, &[0; u16::MAX as usize + 1]), Err(Error::TooLarge(65536))));
+    }
+}
@@ -5,7 +5,15 @@ mod outgoing;
 pub mod packet {
     pub use super::incoming::Incoming;
 
-    pub use super::outgoing::Outgoing;
+    pub use super::outgoing::{Outgoing, Reason};
 }
 
-pub mod io;
\ No newline at end of file
+pub mod io;
+
+mod error;
+
+pub use error::{Error, DecodeError};
+
+mod connection;
+
+pub use connection::{Connection, Event, Inbound};
@@ -1,3 +1,51 @@
+use crate::common::Bytes;
+
+use super::error::DecodeError;
+
+///
+/// Why the server turns a client away.
+/// 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Reason {
+    SpawnFull,
+    InvalidToken,
+    TokenExpired,
+    TokenFromFuture,
+    DuplicateSession,
+    Banned,
+    Shutdown,
+    Admin,
+}
+
+impl Reason {
+    pub fn serial(&self) -> u8 {
+        match self {
+            Reason::SpawnFull => 1,
+            Reason::InvalidToken => 2,
+            Reason::TokenExpired => 3,
+            Reason::TokenFromFuture => 4,
+            Reason::DuplicateSession => 5,
+            Reason::Banned => 6,
+            Reason::Shutdown => 7,
+            Reason::Admin => 8,
+        }
+    }
+
+    pub fn from_serial(serial: u8) -> Option<Self> {
+        match serial {
+            1 => Some(Reason::SpawnFull),
+            2 => Some(Reason::InvalidToken),
+            3 => Some(Reason::TokenExpired),
+            4 => Some(Reason::TokenFromFuture),
+            5 => Some(Reason::DuplicateSession),
+            6 => Some(Reason::Banned),
+            7 => Some(Reason::Shutdown),
+            8 => Some(Reason::Admin),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Outgoing {
     Pong { timestamp: i64 },
@@ -7,32 +55,168 @@ pub enum Outgoing {
     Introduce { users: Vec<([u8; 16], i32, i32, i32)> },
     Move { id: [u8; 16], x: i32, y: i32, z: i32, tick: i64 },
     Arrive { id: [u8; 16], x: i32, y: i32, z: i32 },
+    Enter { id: [u8; 16], x: i32, y: i32, z: i32 },
+    Leave { id: [u8; 16] },
+    Terrain { x: i32, y: i32, z: i32, width: u16, depth: u16, kinds: Vec<u8> },
+    Refuse { reason: Reason },
+    Kick { reason: Reason },
+    Shutdown { reason: Reason, reconnect_after: u32 },
+    Notice { message: String },
 }
 
 impl Outgoing {
+    ///
+    /// Name of the packet a serial stands for, for metrics.
+    /// 
+    pub fn name(serial: u16) -> &'static str {
+        match serial {
+            1 => "pong",
+            2 => "hello",
+            3 => "connect",
+            4 => "disconnect",
+            5 => "introduce",
+            6 => "move",
+            7 => "arrive",
+            8 => "enter",
+            9 => "leave",
+            10 => "terrain",
+            11 => "refuse",
+            12 => "kick",
+            13 => "shutdown",
+            14 => "notice",
+            _ => "unknown",
+        }
+    }
+
+    ///
+    /// Read a body the server sent, the counterpart of `serialize`.
+    /// 
+    pub fn deserialize(buf: &[u8]) -> Result<Self, DecodeError> {
+        if buf.len() < 2 {
+            return Err(DecodeError::TooShort { serial: None, length: buf.len() })
+        }
+
+        let serial = u16::from_le_bytes([buf[0], buf[1]]);
+
+        let body = &buf[2..];
+
+        let too_short = DecodeError::TooShort { serial: Some(serial), length: buf.len() };
+
+        let reason = |value: u8| Reason::from_serial(value).ok_or(DecodeError::InvalidArgument { serial, value });
+
+        match serial {
+            1 => {
+                if body.len() < 8 {
+                    return Err(too_short)
+                }
+
+                Ok(Outgoing::Pong { timestamp: i64::from_le_bytes(body[..8].clone_into_array()) })
+            },
+            2 | 4 | 9 => {
+                if body.len() < 16 {
+                    return Err(too_short)
+                }
+
+                let id = body[..16].clone_into_array();
+
+                Ok(match serial {
+                    2 => Outgoing::Hello { id },
+                    4 => Outgoing::Disconnect { id },
+                    _ => Outgoing::Leave { id },
+                })
+            },
+            3 | 7 | 8 => {
+                if body.len() < 28 {
+                    return Err(too_short)
+                }
+
+                let (id, x, y, z) = read_user(body);
+
+                Ok(match serial {
+                    3 => Outgoing::Connect { id, x, y, z },
+                    7 => Outgoing::Arrive { id, x, y, z },
+                    _ => Outgoing::Enter { id, x, y, z },
+                })
+            },
+            5 => {
+                let users = body.chunks_exact(28);
+
+                if !users.remainder().is_empty() {
+                    return Err(too_short)
+                }
+
+                Ok(Outgoing::Introduce { users: users.map(read_user).collect() })
+            },
+            6 => {
+                if body.len() < 36 {
+                    return Err(too_short)
+                }
+
+                let (id, x, y, z) = read_user(body);
+
+                Ok(Outgoing::Move { id, x, y, z, tick: i64::from_le_bytes(body[28..36].clone_into_array()) })
+            },
+            10 => {
+                if body.len() < 16 {
+                    return Err(too_short)
+                }
+
+                Ok(Outgoing::Terrain {
+                    x: i32::from_le_bytes(body[0..4].clone_into_array()),
+                    y: i32::from_le_bytes(body[4..8].clone_into_array()),
+                    z: i32::from_le_bytes(body[8..12].clone_into_array()),
+                    width: u16::from_le_bytes(body[12..14].clone_into_array()),
+                    depth: u16::from_le_bytes(body[14..16].clone_into_array()),
+                    kinds: body[16..].to_vec(),
+                })
+            },
+            11 | 12 => {
+                if body.is_empty() {
+                    return Err(too_short)
+                }
+
+                let reason = reason(body[0])?;
+
+                Ok(match serial {
+                    11 => Outgoing::Refuse { reason },
+                    _ => Outgoing::Kick { reason },
+                })
+            },
+            13 => {
+                if body.len() < 5 {
+                    return Err(too_short)
+                }
+
+                Ok(Outgoing::Shutdown { reason: reason(body[0])?, reconnect_after: u32::from_le_bytes(body[1..5].clone_into_array()) })
+            },
+            14 => Ok(Outgoing::Notice { message: String::from_utf8_lossy(body).into_owned() }),
+            n => Err(DecodeError::UnknownPacket(n))
+        }
+    }
+
     pub fn serialize(self) -> Vec<u8> {
         match self {
             Outgoing::Pong { timestamp } => [
-                &[1 as u8, 0] as &[u8],
+                &[1u8, 0] as &[u8],
                 &timestamp.to_le_bytes(),
             ].concat(),
             Outgoing::Hello { id } => [
-                &[2 as u8, 0] as &[u8],
+                &[2u8, 0] as &[u8],
                 &id,
             ].concat(),
             Outgoing::Connect { id, x, y, z } => [
-                &[3 as u8, 0] as &[u8],
+                &[3u8, 0] as &[u8],
                 &id,
                 &x.to_le_bytes(),
                 &y.to_le_bytes(),
                 &z.to_le_bytes(),
             ].concat(),
             Outgoing::Disconnect { id } => [
-                &[4 as u8, 0] as &[u8],
+                &[4u8, 0] as &[u8],
                 &id,
             ].concat(),
             Outgoing::Introduce { users } => [
-                &[5 as u8, 0] as &[u8],
+                &[5u8, 0] as &[u8],
                 &users.iter().flat_map(|(id, x, y, z)| [
                     id as &[u8],
                     &x.to_le_bytes(),
@@ -41,7 +225,7 @@ impl Outgoing {
                 ].concat()).collect::<Vec<u8>>()
             ].concat(),
             Outgoing::Move { id, x, y, z, tick } => [
-                &[6 as u8, 0] as &[u8],
+                &[6u8, 0] as &[u8],
                 &id,
                 &x.to_le_bytes(),
                 &y.to_le_bytes(),
@@ -49,12 +233,61 @@ impl Outgoing {
                 &tick.to_le_bytes(),
             ].concat(),
             Outgoing::Arrive { id, x, y, z } => [
-                &[7 as u8, 0] as &[u8],
+                &[7u8, 0] as &[u8],
                 &id,
                 &x.to_le_bytes(),
                 &y.to_le_bytes(),
                 &z.to_le_bytes(),
             ].concat(),
+            Outgoing::Enter { id, x, y, z } => [
+                &[8u8, 0] as &[u8],
+                &id,
+                &x.to_le_bytes(),
+                &y.to_le_bytes(),
+                &z.to_le_bytes(),
+            ].concat(),
+            Outgoing::Leave { id } => [
+                &[9u8, 0] as &[u8],
+                &id,
+            ].concat(),
+            Outgoing::Terrain { x, y, z, width, depth, kinds } => [
+                &[10u8, 0] as &[u8],
+                &x.to_le_bytes(),
+                &y.to_le_bytes(),
+                &z.to_le_bytes(),
+                &width.to_le_bytes(),
+                &depth.to_le_bytes(),
+                &kinds,
+            ].concat(),
+            Outgoing::Refuse { reason } => [
+                &[11u8, 0] as &[u8],
+                &[reason.serial()],
+            ].concat(),
+            Outgoing::Kick { reason } => [
+                &[12u8, 0] as &[u8],
+                &[reason.serial()],
+            ].concat(),
+            Outgoing::Shutdown { reason, reconnect_after } => [
+                &[13u8, 0] as &[u8],
+                &[reason.serial()],
+                &reconnect_after.to_le_bytes(),
+            ].concat(),
+            Outgoing::Notice { message } => [
+                &[14u8, 0] as &[u8],
+                message.as_bytes(),
+            ].concat(),
         }
     }
 }
+
+///
+/// An id followed by a position, as several packets carry them.
+/// 
+fn read_user(body: &[u8]) -> ([u8; 16], i32, i32, i32) {
+    (
+        body[..16].clone_into_array(),
+        i32::from_le_bytes(body[16..20].clone_into_array()),
+        i32::from_le_bytes(body[20..24].clone_into_array()),
+        i32::from_le_bytes(body[24..28].clone_into_array()),
+    )
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashMap, error::Error, fs, io::{self, Write}, path::{Path, PathBuf}};
+
+use crate::common::{Bytes, from_hex, math::Vector3};
+
+use super::{Character, Storage};
+
+///
+/// Storage in a single append-only file.
+/// 
+/// Every save appends a line of `<hex id> <x> <y> <z>`, so the last
+/// line of an id wins when the file is read back. Saving everyone at
+/// once rewrites the file with one line per id to keep it short.
+/// Lines that fail to parse, such as one torn by a crash, are skipped
+/// with a warning.
+/// 
+pub struct FileStorage {
+    path: PathBuf,
+    file: fs::File,
+    characters: HashMap<[u8; 16], Character>,
+}
+
+impl FileStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut characters = HashMap::new();
+
+        let mut is_torn = false;
+
+        if path.exists() {
+            let source = fs::read_to_string(&path)?;
+
+            for (number, line) in source.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match parse_line(line) {
+                    Some((id, character)) => { characters.insert(id, character); },
+                    None => tracing::warn!(path = ?path, line = number + 1, "skipping malformed record, {line:?}"),
+                }
+            }
+
+            is_torn = !source.is_empty() && !source.ends_with('\n');
+        }
+
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+
+        // A save cut short leaves its line unterminated, so start the next one afresh.
+        if is_torn {
+            file.write_all(b"\n")?;
+        }
+
+        Ok(FileStorage { path, file, characters })
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self, id: &[u8; 16]) -> io::Result<Option<Character>> {
+        Ok(self.characters.get(id).copied())
+    }
+
+    fn save(&mut self, id: [u8; 16], character: Character) -> io::Result<()> {
+        self.file.write_all(format_line(&id, &character).as_bytes())?;
+
+        self.characters.insert(id, character);
+
+        Ok(())
+    }
+
+    fn save_all(&mut self, characters: Vec<([u8; 16], Character)>) -> io::Result<()> {
+        self.characters.extend(characters);
+
+        let temporary = self.path.with_extension("tmp");
+
+        let mut file = fs::File::create(&temporary)?;
+
+        for (id, character) in self.characters.iter() {
+            file.write_all(format_line(id, character).as_bytes())?;
+        }
+
+        file.sync_all()?;
+
+        fs::rename(&temporary, &self.path)?;
+
+        self.file = fs::OpenOptions::new().append(true).open(&self.path)?;
+
+        Ok(())
+    }
+}
+
+fn format_line(id: &[u8; 16], character: &Character) -> String {
+    let id = id.to_hex();
+
+    let Vector3 { x, y, z } = character.position;
+
+    format!("{id} {x} {y} {z}\n")
+}
+
+fn parse_line(line: &str) -> Option<([u8; 16], Character)> {
+    let mut words = line.split_whitespace();
+
+    let id = from_hex(words.next()?)?.try_into().ok()?;
+
+    let x = words.next()?.parse().ok()?;
+    let y = words.next()?.parse().ok()?;
+    let z = words.next()?.parse().ok()?;
+
+    Some((id, Character { position: Vector3::new(x, y, z) }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: [u8; 16] = [0xa; 16];
+    const B: [u8; 16] = [0xb; 16];
+
+    fn at(x: i32, z: i32) -> Character {
+        Character { position: Vector3::new(x, 0, z) }
+    }
+
+    fn position(storage: &FileStorage, id: &[u8; 16]) -> Option<Vector3> {
+        storage.load(id).unwrap().map(|character| character.position)
+    }
+
+    #[test]
+    fn saves_survive_a_reopen() {
+        let data = tempfile::tempdir().unwrap();
+
+        let path = data.path().join("characters");
+
+        let mut storage = FileStorage::open(&path).unwrap();
+
+        storage.save(A, at(1, 2)).unwrap();
+
+        storage.save(B, at(3, 4)).unwrap();
+
+        storage.save(A, at(5, 6)).unwrap();
+
+        let storage = FileStorage::open(&path).unwrap();
+
+        assert_eq!(position(&storage, &A), Some(Vector3::new(5, 0, 6)));
+
+        assert_eq!(position(&storage, &B), Some(Vector3::new(3, 0, 4)));
+
+        assert_eq!(position(&storage, &[0xc; 16]), None);
+    }
+
+    #[test]
+    fn saving_everyone_rewrites_one_line_per_id() {
+        let data = tempfile::tempdir().unwrap();
+
+        let path = data.path().join("characters");
+
+        let mut storage = FileStorage::open(&path).unwrap();
+
+        for x in 0..3 {
+            storage.save(A, at(x, 0)).unwrap();
+        }
+
+        storage.save(B, at(7, 7)).unwrap();
+
+        storage.save_all(vec![(A, at(9, 9))]).unwrap();
+
+        let mut lines = fs::read_to_string(&path).unwrap().lines().map(str::to_owned).collect::<Vec<_>>();
+
+        lines.sort();
+
+        assert_eq!(lines, vec![format!("{} 9 0 9", A.to_hex()), format!("{} 7 0 7", B.to_hex())]);
+
+        // Saves after the rewrite still go to the file.
+        storage.save(B, at(8, 8)).unwrap();
+
+        let storage = FileStorage::open(&path).unwrap();
+
+        assert_eq!(position(&storage, &A), Some(Vector3::new(9, 0, 9)));
+
+        assert_eq!(position(&storage, &B), Some(Vector3::new(8, 0, 8)));
+    }
+
+    #[test]
+    fn malformed_and_torn_lines_are_skipped() {
+        let data = tempfile::tempdir().unwrap();
+
+        let path = data.path().join("characters");
+
+        fs::write(&path, format!("{} 1 0 1\nnot a record\n{} 2 0", A.to_hex(), B.to_hex())).unwrap();
+
+        let mut storage = FileStorage::open(&path).unwrap();
+
+        assert_eq!(position(&storage, &A), Some(Vector3::new(1, 0, 1)));
+
+        assert_eq!(position(&storage, &B), None);
+
+        storage.save(B, at(3, 3)).unwrap();
+
+        let storage = FileStorage::open(&path).unwrap();
+
+        assert_eq!(position(&storage, &B), Some(Vector3::new(3, 0, 3)));
+    }
+}
@@ -0,0 +1,29 @@
+use std::{collections::HashMap, io};
+
+use super::{Character, Storage};
+
+///
+/// Storage forgetting everything when the server stops, for tests and trials.
+/// 
+#[derive(Default)]
+pub struct MemoryStorage {
+    characters: HashMap<[u8; 16], Character>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self, id: &[u8; 16]) -> io::Result<Option<Character>> {
+        Ok(self.characters.get(id).copied())
+    }
+
+    fn save(&mut self, id: [u8; 16], character: Character) -> io::Result<()> {
+        self.characters.insert(id, character);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use std::io;
+
+use crate::common::math::Vector3;
+
+mod file;
+
+mod memory;
+
+pub use file::FileStorage;
+
+pub use memory::MemoryStorage;
+
+///
+/// What is kept of a character between sessions.
+/// 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Character {
+    pub position: Vector3,
+}
+
+///
+/// Where characters live while their players are away, keyed by account id.
+/// 
+pub trait Storage {
+    fn load(&self, id: &[u8; 16]) -> io::Result<Option<Character>>;
+
+    fn save(&mut self, id: [u8; 16], character: Character) -> io::Result<()>;
+
+    ///
+    /// Save many characters at once, as an autosave does.
+    /// 
+    fn save_all(&mut self, characters: Vec<([u8; 16], Character)>) -> io::Result<()> {
+        for (id, character) in characters {
+            self.save(id, character)?;
+        }
+
+        Ok(())
+    }
+}
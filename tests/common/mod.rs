@@ -0,0 +1,255 @@
+//!
+//! A server on an ephemeral port of localhost, driven by scripted clients.
+//!
+//! Tests run on tokio's paused clock, and the harness keeps the runtime
+//! busy while it waits on sockets, so the clock never skips ahead on its
+//! own. Time only passes when a test advances it, which makes movement
+//! ticks fire exactly when the script says.
+//!
+
+use std::{future::{self, Future}, net::SocketAddr, path::PathBuf, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+
+use mmorpg::{admin::{Command, Request}, auth::{self, BanList, Roles}, client::Client, constants::Constants, handler::Context, map::loader, net::packet::Outgoing, storage::FileStorage};
+use tokio::{io::AsyncReadExt, net::{TcpListener, TcpStream}, sync::{mpsc, oneshot}, task};
+
+///
+/// How many times to yield to the server for a packet before giving up.
+///
+/// Localhost delivers at once, so this only has to cover the hops
+/// through the reader, the game loop and the writer.
+///
+const PATIENCE: usize = 10_000;
+
+///
+/// A strip of ground five tiles long, with spawn points
+/// at x 0 and x 2 handed out in turn.
+///
+pub const STRIP: &str = "\
+size 5 1
+spawn strip 0 0
+spawn strip 2 0
+grid
+.....
+";
+
+///
+/// A strip thirty tiles long, with spawn points at x 0 and x 12,
+/// a step further apart than the default view radius of 10.
+///
+pub const FIELD: &str = "\
+size 30 1
+spawn field 0 0
+spawn field 12 0
+grid
+..............................
+";
+
+pub struct Server {
+    address: SocketAddr,
+    admin: mpsc::Sender<Request>,
+    shutdown: mpsc::Sender<()>,
+    is_stopping: Arc<AtomicBool>,
+    pub bans_path: PathBuf,
+    pub storage_path: PathBuf,
+}
+
+impl Server {
+    ///
+    /// Connect as the account `id` and take the hello.
+    ///
+    pub async fn connect(&self, id: [u8; 16]) -> Client {
+        self.connect_as(id, Roles::NONE).await
+    }
+
+    ///
+    /// Connect as the account `id` holding `roles`.
+    ///
+    pub async fn connect_as(&self, id: [u8; 16], roles: Roles) -> Client {
+        let token = auth::issue(id, auth::now_millis(), roles, "secret").unwrap();
+
+        match settle(Client::connect(self.address, &token)).await {
+            Some(Ok(client)) => client,
+            Some(Err(e)) => panic!("failed to connect, {e}"),
+            None => panic!("no hello"),
+        }
+    }
+
+    ///
+    /// Where the server listens.
+    ///
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    ///
+    /// Open a socket that never says hello.
+    ///
+    pub async fn open(&self) -> TcpStream {
+        TcpStream::connect(self.address).await.unwrap()
+    }
+
+    ///
+    /// Ask the server to shut down, as a signal would.
+    ///
+    /// It stops once the notices are queued, so clients see their
+    /// sockets close after the final save.
+    ///
+    pub async fn shut_down(&self) {
+        self.is_stopping.store(true, Ordering::Relaxed);
+
+        self.shutdown.send(()).await.unwrap();
+    }
+
+    ///
+    /// Run `command` as an operator would, and take the answer.
+    ///
+    pub async fn admin(&self, command: Command) -> String {
+        let (reply, answer) = oneshot::channel();
+
+        if self.admin.send(Request { command, reply }).await.is_err() {
+            panic!("server stopped taking admin commands");
+        }
+
+        match settle(answer).await {
+            Some(Ok(answer)) => answer,
+            Some(Err(e)) => panic!("admin command dropped, {e}"),
+            None => panic!("no answer to the admin command"),
+        }
+    }
+}
+
+///
+/// Boot a server on `map` and run `script` against it,
+/// failing if the server stops before the script shuts it down.
+///
+pub async fn run<F, S>(map: &str, script: S) where F: Future<Output = ()>, S: FnOnce(Server) -> F {
+    run_with(map, "", script).await
+}
+
+///
+/// Like `run`, with `settings` added to the configuration.
+///
+pub async fn run_with<F, S>(map: &str, settings: &str, script: S) where F: Future<Output = ()>, S: FnOnce(Server) -> F {
+    // Removed with everything in it once the script is over.
+    let data = tempfile::tempdir().unwrap();
+
+    let bans_path = data.path().join("bans");
+
+    let storage_path = data.path().join("characters");
+
+    let admin_socket = data.path().join("admin.sock");
+
+    let constants = Constants::from_toml(&format!("\
+auth_secret = \"secret\"
+spawn_policy = \"round-robin\"
+bans_path = {bans_path:?}
+storage_path = {storage_path:?}
+admin_socket = {admin_socket:?}
+{settings}
+")).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    let address = listener.local_addr().unwrap();
+
+    let bans = BanList::open(&constants.bans_path).unwrap();
+
+    let context = Context::new(constants, loader::parse(map).unwrap(), Box::new(FileStorage::open(&storage_path).unwrap()), bans, listener).unwrap();
+
+    let admin = context.admin();
+
+    let shutdown = context.shutdown();
+
+    let is_stopping = Arc::new(AtomicBool::new(false));
+
+    let serve = async {
+        let result = context.run().await;
+
+        if !is_stopping.load(Ordering::Relaxed) {
+            panic!("server stopped, {result:?}");
+        }
+
+        if let Err(e) = result {
+            panic!("server failed to shut down, {e}");
+        }
+
+        future::pending::<()>().await
+    };
+
+    tokio::select! {
+        _ = serve => {},
+        _ = script(Server { address, admin, shutdown, is_stopping: is_stopping.clone(), bans_path, storage_path }) => {},
+    }
+}
+
+///
+/// The next packet, failing when none comes.
+///
+pub async fn next(client: &mut Client) -> Outgoing {
+    match settle(client.next()).await {
+        Some(Ok(Some(packet))) => packet,
+        Some(Ok(None)) => panic!("connection closed"),
+        Some(Err(e)) => panic!("client failed, {e}"),
+        None => panic!("no packet arrived"),
+    }
+}
+
+///
+/// The next packet other than terrain, which every welcome carries.
+///
+pub async fn next_event(client: &mut Client) -> Outgoing {
+    loop {
+        match next(client).await {
+            Outgoing::Terrain { .. } => continue,
+            packet => return packet,
+        }
+    }
+}
+
+///
+/// Make sure nothing but terrain arrives while the clock stands still.
+///
+pub async fn expect_silence(client: &mut Client) {
+    loop {
+        match settle(client.next()).await {
+            Some(Ok(Some(Outgoing::Terrain { .. }))) => continue,
+            Some(Ok(Some(packet))) => panic!("unexpected packet, {packet:?}"),
+            Some(result) => panic!("connection ended, {result:?}"),
+            None => return,
+        }
+    }
+}
+
+///
+/// Whether the server closed `stream`, without waiting on the clock.
+///
+pub async fn is_closed(stream: &mut TcpStream) -> bool {
+    let mut buf = [0; 64];
+
+    match settle(stream.read(&mut buf)).await {
+        Some(Ok(0) | Err(_)) => true,
+        Some(Ok(n)) => panic!("unexpected {n} bytes"),
+        None => false,
+    }
+}
+
+///
+/// Poll `future` while yielding to the server, `None` if it is still
+/// pending after `PATIENCE` turns.
+///
+/// Yielding keeps the runtime from parking, which on a paused
+/// clock would let it jump to the next timer.
+///
+async fn settle<F: Future>(future: F) -> Option<F::Output> {
+    tokio::pin!(future);
+
+    for _ in 0..PATIENCE {
+        tokio::select! {
+            biased;
+            output = &mut future => return Some(output),
+            _ = task::yield_now() => {},
+        }
+    }
+
+    None
+}
@@ -0,0 +1,448 @@
+mod common;
+
+use std::time::Duration;
+
+use mmorpg::{admin::Command, auth::Roles, common::math::Vector3, net::packet::{Incoming, Outgoing, Reason}, storage::{FileStorage, Storage}};
+use tokio::{net::TcpStream, time};
+
+use common::{FIELD, STRIP, expect_silence, is_closed, next, next_event};
+
+const A: [u8; 16] = [0xa; 16];
+const B: [u8; 16] = [0xb; 16];
+
+#[tokio::test(start_paused = true)]
+async fn handshake_introduces_the_player() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect(A).await;
+
+        assert_eq!(a.id(), A);
+
+        match next(&mut a).await {
+            Outgoing::Introduce { users } => assert_eq!(users, vec![(A, 0, 0, 0)]),
+            packet => panic!("expected introduce, {packet:?}"),
+        }
+
+        expect_silence(&mut a).await;
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn silent_sockets_are_evicted_after_the_handshake_deadline() {
+    common::run(STRIP, |server| async move {
+        let mut silent = server.open().await;
+
+        // Settles until the server accepted it, so the deadline starts now.
+        assert!(!is_closed(&mut silent).await);
+
+        time::advance(Duration::from_millis(9_999)).await;
+
+        assert!(!is_closed(&mut silent).await);
+
+        time::advance(Duration::from_millis(1)).await;
+
+        assert!(is_closed(&mut silent).await);
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn sockets_beyond_max_waitings_are_closed_at_once() {
+    common::run_with(STRIP, "max_waitings = 1", |server| async move {
+        let mut first = server.open().await;
+
+        assert!(!is_closed(&mut first).await);
+
+        let mut second = server.open().await;
+
+        assert!(is_closed(&mut second).await);
+
+        // Evicting the waiting one makes room again.
+        time::advance(Duration::from_secs(10)).await;
+
+        assert!(is_closed(&mut first).await);
+
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn introduce_lists_players_nearby() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+
+        let mut b = server.connect(B).await;
+
+        match next_event(&mut b).await {
+            Outgoing::Introduce { users } => assert_eq!(users, vec![(B, 2, 0, 0), (A, 0, 0, 0)]),
+            packet => panic!("expected introduce, {packet:?}"),
+        }
+
+        match next_event(&mut a).await {
+            Outgoing::Connect { id, x, y, z } => assert_eq!((id, x, y, z), (B, 2, 0, 0)),
+            packet => panic!("expected connect, {packet:?}"),
+        }
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn players_out_of_sight_hear_nothing_of_each_other() {
+    common::run(FIELD, |server| async move {
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+
+        let mut b = server.connect(B).await;
+
+        match next_event(&mut b).await {
+            Outgoing::Introduce { users } => assert_eq!(users, vec![(B, 12, 0, 0)]),
+            packet => panic!("expected introduce, {packet:?}"),
+        }
+
+        // b spawned 12 tiles away, so a hears of no connect.
+        expect_silence(&mut a).await;
+
+        b.walk(3).await.unwrap();
+
+        match next_event(&mut b).await {
+            Outgoing::Move { id, x, .. } => assert_eq!((id, x), (B, 11)),
+            packet => panic!("expected move, {packet:?}"),
+        }
+
+        expect_silence(&mut a).await;
+
+        time::advance(Duration::from_millis(300)).await;
+
+        // Stepping onto x 10 brings both into the radius.
+        match next_event(&mut a).await {
+            Outgoing::Enter { id, x, y, z } => assert_eq!((id, x, y, z), (B, 10, 0, 0)),
+            packet => panic!("expected enter, {packet:?}"),
+        }
+
+        let (mut moved, mut entered) = (false, false);
+
+        for _ in 0..2 {
+            match next_event(&mut b).await {
+                Outgoing::Move { id, x, .. } if (id, x) == (B, 10) => moved = true,
+                Outgoing::Enter { id, x, y, z } if (id, x, y, z) == (A, 0, 0, 0) => entered = true,
+                packet => panic!("expected move and enter, {packet:?}"),
+            }
+        }
+
+        assert!(moved && entered);
+
+        b.walk(4).await.unwrap();
+
+        expect_silence(&mut a).await;
+
+        time::advance(Duration::from_millis(300)).await;
+
+        // And stepping back onto x 11 takes both out of it.
+        match next_event(&mut a).await {
+            Outgoing::Leave { id } => assert_eq!(id, B),
+            packet => panic!("expected leave, {packet:?}"),
+        }
+
+        let (mut moved, mut left) = (false, false);
+
+        for _ in 0..2 {
+            match next_event(&mut b).await {
+                Outgoing::Move { id, x, .. } if (id, x) == (B, 11) => moved = true,
+                Outgoing::Leave { id } if id == A => left = true,
+                packet => panic!("expected move and leave, {packet:?}"),
+            }
+        }
+
+        assert!(moved && left);
+
+        b.walk(0).await.unwrap();
+
+        expect_silence(&mut a).await;
+
+        time::advance(Duration::from_millis(300)).await;
+
+        match next_event(&mut b).await {
+            Outgoing::Arrive { id, x, .. } => assert_eq!((id, x), (B, 11)),
+            packet => panic!("expected arrive, {packet:?}"),
+        }
+
+        expect_silence(&mut a).await;
+
+        b.close().await.unwrap();
+
+        // Nor of the disconnect.
+        expect_silence(&mut a).await;
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn moves_are_broadcast_and_stop_at_players() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+
+        let mut b = server.connect(B).await;
+
+        next_event(&mut b).await;
+
+        next_event(&mut a).await;
+
+        a.walk(4).await.unwrap();
+
+        for client in [&mut a, &mut b] {
+            match next_event(client).await {
+                Outgoing::Move { id, x, y, z, tick } => assert_eq!((id, x, y, z, tick), (A, 1, 0, 0, 300)),
+                packet => panic!("expected move, {packet:?}"),
+            }
+        }
+
+        time::advance(Duration::from_millis(299)).await;
+
+        expect_silence(&mut a).await;
+
+        time::advance(Duration::from_millis(1)).await;
+
+        // The next step would be onto b, so a stops where it is.
+        for client in [&mut a, &mut b] {
+            match next_event(client).await {
+                Outgoing::Arrive { id, x, y, z } => assert_eq!((id, x, y, z), (A, 1, 0, 0)),
+                packet => panic!("expected arrive, {packet:?}"),
+            }
+        }
+
+        time::advance(Duration::from_millis(300)).await;
+
+        expect_silence(&mut a).await;
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn disconnect_frees_the_tile() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+
+        let mut b = server.connect(B).await;
+
+        next_event(&mut b).await;
+
+        next_event(&mut a).await;
+
+        b.close().await.unwrap();
+
+        match next_event(&mut a).await {
+            Outgoing::Disconnect { id } => assert_eq!(id, B),
+            packet => panic!("expected disconnect, {packet:?}"),
+        }
+
+        a.walk(4).await.unwrap();
+
+        for x in 1..=4 {
+            match next_event(&mut a).await {
+                Outgoing::Move { id, x: to, .. } => assert_eq!((id, to), (A, x)),
+                packet => panic!("expected move, {packet:?}"),
+            }
+
+            time::advance(Duration::from_millis(300)).await;
+        }
+
+        match next_event(&mut a).await {
+            Outgoing::Arrive { id, x, .. } => assert_eq!((id, x), (A, 4)),
+            packet => panic!("expected arrive, {packet:?}"),
+        }
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn reconnecting_player_comes_back_where_they_left() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+
+        a.walk(4).await.unwrap();
+
+        next_event(&mut a).await;
+
+        a.walk(0).await.unwrap();
+
+        a.close().await.unwrap();
+
+        let mut a = server.connect(A).await;
+
+        match next_event(&mut a).await {
+            Outgoing::Introduce { users } => assert_eq!(users, vec![(A, 1, 0, 0)]),
+            packet => panic!("expected introduce, {packet:?}"),
+        }
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn second_session_replaces_the_first() {
+    common::run_with(STRIP, "duplicate_session = \"replace\"", |server| async move {
+        let mut first = server.connect(A).await;
+
+        next_event(&mut first).await;
+
+        let mut second = server.connect(A).await;
+
+        match next_event(&mut first).await {
+            Outgoing::Kick { reason } => assert_eq!(reason, Reason::DuplicateSession),
+            packet => panic!("expected kick, {packet:?}"),
+        }
+
+        match next_event(&mut second).await {
+            Outgoing::Introduce { users } => assert_eq!(users, vec![(A, 0, 0, 0)]),
+            packet => panic!("expected introduce, {packet:?}"),
+        }
+
+        expect_silence(&mut second).await;
+
+        // The new session outlives whatever was queued for the old one.
+        second.walk(4).await.unwrap();
+
+        match next_event(&mut second).await {
+            Outgoing::Move { id, x, .. } => assert_eq!((id, x), (A, 1)),
+            packet => panic!("expected move, {packet:?}"),
+        }
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn second_session_is_refused_while_the_first_plays() {
+    common::run_with(STRIP, "duplicate_session = \"refuse\"", |server| async move {
+        let mut first = server.connect(A).await;
+
+        next_event(&mut first).await;
+
+        let mut second = server.connect(A).await;
+
+        match next(&mut second).await {
+            Outgoing::Refuse { reason } => assert_eq!(reason, Reason::DuplicateSession),
+            packet => panic!("expected refuse, {packet:?}"),
+        }
+
+        expect_silence(&mut first).await;
+
+        first.walk(4).await.unwrap();
+
+        match next_event(&mut first).await {
+            Outgoing::Move { id, x, .. } => assert_eq!((id, x), (A, 1)),
+            packet => panic!("expected move, {packet:?}"),
+        }
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn shutdown_notifies_saves_and_stops_accepting() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+
+        a.walk(4).await.unwrap();
+
+        next_event(&mut a).await;
+
+        a.walk(0).await.unwrap();
+
+        expect_silence(&mut a).await;
+
+        time::advance(Duration::from_millis(300)).await;
+
+        match next_event(&mut a).await {
+            Outgoing::Arrive { id, x, .. } => assert_eq!((id, x), (A, 1)),
+            packet => panic!("expected arrive, {packet:?}"),
+        }
+
+        let mut silent = server.open().await;
+
+        assert!(!is_closed(&mut silent).await);
+
+        server.shut_down().await;
+
+        match next_event(&mut a).await {
+            Outgoing::Shutdown { reason, reconnect_after } => assert_eq!((reason, reconnect_after), (Reason::Shutdown, 30)),
+            packet => panic!("expected shutdown, {packet:?}"),
+        }
+
+        assert!(matches!(time::timeout(Duration::from_secs(1), a.next()).await, Ok(Ok(None))));
+
+        assert!(is_closed(&mut silent).await);
+
+        assert!(TcpStream::connect(server.address()).await.is_err());
+
+        // Nothing saved a's step but the final save.
+        let storage = FileStorage::open(&server.storage_path).unwrap();
+
+        assert_eq!(storage.load(&A).unwrap().map(|character| character.position), Some(Vector3::new(1, 0, 0)));
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn only_moderators_may_announce() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect_as(A, Roles::MODERATOR).await;
+
+        next_event(&mut a).await;
+
+        let mut b = server.connect(B).await;
+
+        next_event(&mut b).await;
+
+        next_event(&mut a).await;
+
+        b.send(Incoming::Announce { message: String::from("from a player") }).await.unwrap();
+
+        expect_silence(&mut a).await;
+
+        a.send(Incoming::Announce { message: String::from("from staff") }).await.unwrap();
+
+        for client in [&mut a, &mut b] {
+            match next_event(client).await {
+                Outgoing::Notice { message } => assert_eq!(message, "from staff"),
+                packet => panic!("expected notice, {packet:?}"),
+            }
+        }
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn banning_a_player_kicks_them_at_once() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+
+        server.admin(Command::Ban { id: A, until: None, reason: String::from("cheating") }).await;
+
+        match next_event(&mut a).await {
+            Outgoing::Kick { reason } => assert_eq!(reason, Reason::Banned),
+            packet => panic!("expected kick, {packet:?}"),
+        }
+    }).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn edited_ban_list_is_enforced_on_reload() {
+    common::run(STRIP, |server| async move {
+        let mut a = server.connect(A).await;
+
+        next_event(&mut a).await;
+
+        std::fs::write(&server.bans_path, "// kept out\nnot a ban\n0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a  -  cheating\n").unwrap();
+
+        expect_silence(&mut a).await;
+
+        time::advance(Duration::from_secs(5)).await;
+
+        match next_event(&mut a).await {
+            Outgoing::Kick { reason } => assert_eq!(reason, Reason::Banned),
+            packet => panic!("expected kick, {packet:?}"),
+        }
+    }).await;
+}